@@ -1,9 +1,9 @@
+use chrono::Local;
 use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 use std::process::Command;
-use chrono::{Local};
 
 fn main() {
     // Get build profile
@@ -18,18 +18,32 @@ fn main() {
     let dest_path = Path::new(&out_dir).join("version_info.rs");
     let mut f = File::create(dest_path).unwrap();
 
-    // 获取当前的git commit id
-    let output = Command::new("git")
-        .args(["rev-parse", "HEAD"])
-        .output()
-        .expect("Failed to execute git command")
-        .stdout;
-
-    let str = String::from_utf8(output).unwrap();
-
-    let git_hash = str.trim();
+    // Build again when the commit or GIT_HASH changes, a commit moves the branch HEAD points to
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Some(head_ref) = std::fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+    {
+        println!("cargo:rerun-if-changed=.git/{head_ref}");
+    }
 
+    // 获取当前的git commit id, GIT_HASH wins when set, e.g. in builds without a git checkout
+    let git_hash = env::var("GIT_HASH").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+    });
+    let git_hash = git_hash
+        .as_deref()
+        .map(str::trim)
+        .filter(|hash| !hash.is_empty());
 
     // Write version info
-    f.write_all(format!("const VERSION: &str = \"Ver beta.{profile} commit id: {} build on {}\"; const IS_RELEASE: bool = {};", git_hash, date.trim(), is_release).as_bytes()).unwrap();
-}
\ No newline at end of file
+    f.write_all(format!("#[allow(dead_code)] const VERSION: &str = \"Ver beta.{profile} commit id: {} build on {}\"; const IS_RELEASE: bool = {};", git_hash.unwrap_or_default(), date.trim(), is_release).as_bytes()).unwrap();
+    f.write_all(format!(" const GIT_HASH: Option<&str> = {:?}; const BUILD_PROFILE: &str = {:?}; const BUILD_DATE: &str = {:?};", git_hash, profile, date.trim()).as_bytes()).unwrap();
+}
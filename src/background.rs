@@ -0,0 +1,71 @@
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use log::LevelFilter;
+
+use crate::config::LogConfig;
+use crate::event::LogEvent;
+use crate::log_util::LogUtil;
+
+/// Records queued before `Log::log` of an async logger waits for the writer thread
+pub(crate) const QUEUE_CAPACITY: usize = 4096;
+
+enum Message {
+    /// A record that passed the level filter, with the configuration and the logger's level
+    /// for it at the time it was logged
+    Record(LogEvent, Arc<LogConfig>, LevelFilter),
+    /// Answered once every record queued before it is written
+    Drained(SyncSender<()>),
+}
+
+/// The writer thread of a `LogUtil::new_async` logger and the bounded queue feeding it.
+/// `logger` shares the files of the async logger and does the actual writes and rollovers.
+pub(crate) struct BackgroundWriter {
+    sender: SyncSender<Message>,
+    thread: JoinHandle<()>,
+    pub(crate) logger: Arc<LogUtil>,
+}
+
+impl BackgroundWriter {
+    pub(crate) fn spawn(logger: Arc<LogUtil>) -> BackgroundWriter {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let writer = Arc::clone(&logger);
+        let thread = thread::spawn(move || {
+            for message in receiver {
+                match message {
+                    Message::Record(event, config, level) => {
+                        writer.write_event(&config, event, level)
+                    }
+                    Message::Drained(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        BackgroundWriter {
+            sender,
+            thread,
+            logger,
+        }
+    }
+
+    /// Queue `event`, waiting for room when the queue is full
+    pub(crate) fn send(&self, event: LogEvent, config: Arc<LogConfig>, level: LevelFilter) {
+        let _ = self.sender.send(Message::Record(event, config, level));
+    }
+
+    /// Wait until every record queued so far is written
+    pub(crate) fn drain(&self) {
+        let (done, drained) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Drained(done)).is_ok() {
+            let _ = drained.recv();
+        }
+    }
+
+    /// Close the queue and wait for the thread to write the remaining records
+    pub(crate) fn stop(self) {
+        drop(self.sender);
+        let _ = self.thread.join();
+    }
+}
@@ -0,0 +1,140 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::clock::{Clock, SystemClock};
+use crate::config::{LogConfig, MainLogMode, TimeZoneMode};
+use crate::log_util::{default_log_root, expand_env_vars, LogUtil, DEFAULT_DIR_MODE};
+
+/// Chainable options for creating a `LogUtil`, returned by `LogUtil::builder`.
+///
+/// Options that are not set keep the defaults of `LogUtil::new`.
+#[derive(Debug, Clone)]
+pub struct LogUtilBuilder {
+    class_name: &'static str,
+    root_dir: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    dir_mode: u32,
+    with_files: bool,
+    color: Option<bool>,
+    time_zone: TimeZoneMode,
+    main_log_mode: MainLogMode,
+    main_log: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for LogUtilBuilder {
+    fn default() -> Self {
+        LogUtilBuilder {
+            class_name: "",
+            root_dir: None,
+            log_dir: None,
+            dir_mode: DEFAULT_DIR_MODE,
+            with_files: true,
+            color: None,
+            time_zone: TimeZoneMode::Local,
+            main_log_mode: MainLogMode::Truncate,
+            main_log: true,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl LogUtilBuilder {
+    /// Name of the log directory and the log files, so it can't contain path separators or be
+    /// `..`. Without a class name only the console is written.
+    pub fn class_name(mut self, class_name: &'static str) -> Self {
+        self.class_name = class_name;
+        self
+    }
+
+    /// Directory holding the `<class_name>/` log directory, see `LogUtil::new_in`
+    pub fn root_dir(mut self, root_dir: PathBuf) -> Self {
+        self.root_dir = Some(root_dir);
+        self
+    }
+
+    /// Write the log files directly to `log_dir`, e.g. `/var/log/myapp`, instead of to
+    /// `<root>/<class_name>/`. Environment variables written as `$NAME` or `${NAME}` are
+    /// replaced by their values when the logger is built, e.g. `${STATE_DIRECTORY}/logs`.
+    pub fn log_dir(mut self, log_dir: PathBuf) -> Self {
+        self.log_dir = Some(log_dir);
+        self
+    }
+
+    /// Permission bits of created log directories on Unix, see `LogUtil::new_with_dir_mode`
+    pub fn dir_mode(mut self, dir_mode: u32) -> Self {
+        self.dir_mode = dir_mode;
+        self
+    }
+
+    /// Write the latest and dated log files, `true` by default. Without files only the
+    /// console is written, even with a class name.
+    pub fn with_files(mut self, with_files: bool) -> Self {
+        self.with_files = with_files;
+        self
+    }
+
+    /// Color the console output, by default only when stdout is a terminal
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Time zone of the timestamps and of the dated file names, `TimeZoneMode::Local` by
+    /// default. Setting it here names the first dated file after the date of this zone.
+    pub fn time_zone(mut self, time_zone: TimeZoneMode) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Whether `<class_name>.log` is emptied on start and at rollover, appended to or only
+    /// keeps the latest line, `MainLogMode::Truncate` by default
+    pub fn main_log_mode(mut self, main_log_mode: MainLogMode) -> Self {
+        self.main_log_mode = main_log_mode;
+        self
+    }
+
+    /// Write the latest file `<class_name>.log`, `true` by default. Without it only the dated
+    /// `<class_name>_YYYYMMDD.log` files are written and `LogUtil::current_log_path` is `None`.
+    pub fn main_log(mut self, main_log: bool) -> Self {
+        self.main_log = main_log;
+        self
+    }
+
+    /// Where the logger reads the current time, `SystemClock` by default. A `MockClock` makes
+    /// the timestamps and the rollover deterministic in tests.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Create the logger, returning an error naming the path of a log directory or file that
+    /// can't be created
+    pub fn build(self) -> io::Result<LogUtil> {
+        let mut config = LogConfig {
+            time_zone: self.time_zone,
+            main_log_mode: self.main_log_mode,
+            main_log: self.main_log,
+            ..LogConfig::default()
+        };
+        if let Some(color) = self.color {
+            config.color = color;
+        }
+        let log_dir = match self.log_dir {
+            Some(log_dir) => expand_env_vars(&log_dir)?,
+            None => self
+                .root_dir
+                .unwrap_or_else(default_log_root)
+                .join(self.class_name),
+        };
+        LogUtil::try_create(
+            self.class_name,
+            log_dir,
+            self.dir_mode,
+            self.with_files,
+            config,
+            self.clock,
+        )
+    }
+}
@@ -0,0 +1,64 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::log_util::write_full_line;
+
+/// The extra files records are currently teed to, shared by a logger and its capture handles
+#[derive(Default)]
+pub(crate) struct CaptureSinks {
+    next_id: AtomicU64,
+    files: Mutex<Vec<(u64, Arc<Mutex<File>>)>>,
+}
+
+impl CaptureSinks {
+    pub(crate) fn start(sinks: &Arc<CaptureSinks>, path: &Path) -> io::Result<CaptureHandle> {
+        let file = Arc::new(Mutex::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?,
+        ));
+        let id = sinks.next_id.fetch_add(1, Ordering::Relaxed);
+        sinks.files.lock().unwrap().push((id, Arc::clone(&file)));
+        Ok(CaptureHandle {
+            id,
+            sinks: Arc::clone(sinks),
+            file,
+        })
+    }
+
+    /// Write one formatted line to every active capture file
+    pub(crate) fn write_line(&self, line: &str) {
+        for (_, file) in self.files.lock().unwrap().iter() {
+            let _ = write_full_line(&mut *file.lock().unwrap(), line);
+        }
+    }
+}
+
+/// An active capture session created by `LogUtil::start_capture`.
+/// Records are written to the capture file until the handle is stopped or dropped.
+pub struct CaptureHandle {
+    id: u64,
+    sinks: Arc<CaptureSinks>,
+    file: Arc<Mutex<File>>,
+}
+
+impl CaptureHandle {
+    /// Stop capturing and flush the capture file. Same as dropping the handle.
+    pub fn stop(self) {}
+}
+
+impl Drop for CaptureHandle {
+    fn drop(&mut self) {
+        self.sinks
+            .files
+            .lock()
+            .unwrap()
+            .retain(|(id, _)| *id != self.id);
+        let _ = self.file.lock().unwrap().flush();
+    }
+}
@@ -0,0 +1,53 @@
+//! Source of the current time of a `LogUtil`.
+//!
+//! Timestamps, the dated file names and the rollover all read the time through a `Clock`, so
+//! tests can cross midnight with a `MockClock` instead of waiting for it.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Local};
+
+/// Tells a logger what time it is
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The system clock, used unless another clock is given to `LogUtilBuilder::clock`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the time, so a test can keep one and
+/// give another to the logger.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    time: Arc<Mutex<DateTime<Local>>>,
+}
+
+impl MockClock {
+    pub fn new(time: DateTime<Local>) -> MockClock {
+        MockClock {
+            time: Arc::new(Mutex::new(time)),
+        }
+    }
+
+    pub fn set(&self, time: DateTime<Local>) {
+        *self.time.lock().unwrap() = time;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.time.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.time.lock().unwrap()
+    }
+}
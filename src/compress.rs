@@ -0,0 +1,85 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Compress the file at `path` to `<path>.gz` and remove the plaintext.
+/// The archive is written to `<path>.gz.tmp` and only renamed into place once complete,
+/// so on any failure the original file is kept and no partial archive is left behind.
+pub(crate) fn compress_file(path: &Path) -> io::Result<PathBuf> {
+    let gz_path = with_suffix(path, ".gz");
+    let tmp_path = with_suffix(path, ".gz.tmp");
+    let result = File::open(path).and_then(|mut input| {
+        let mut encoder = GzEncoder::new(File::create(&tmp_path)?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?.sync_all()?;
+        fs::rename(&tmp_path, &gz_path)
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    fs::remove_file(path)?;
+    Ok(gz_path)
+}
+
+/// Compress the existing files of `paths` one after another on a new thread.
+/// Failures are reported on stderr, the logging path never waits for the compression.
+pub(crate) fn compress_in_background(paths: Vec<PathBuf>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for path in paths.into_iter().filter(|path| path.exists()) {
+            if let Err(e) = compress_file(&path) {
+                eprintln!("Compressing log file {} failed: {}", path.display(), e);
+            }
+        }
+    })
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::read_to_string_lossy;
+
+    #[test]
+    fn test_compress_file() {
+        let dir = Path::new("log").join("CompressTest");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("CompressTest_20240508.log");
+        fs::write(&path, "first line\nsecond line\n").unwrap();
+
+        let gz_path = compress_file(&path).unwrap();
+        assert_eq!(gz_path, dir.join("CompressTest_20240508.log.gz"));
+        assert!(!path.exists());
+        assert!(!dir.join("CompressTest_20240508.log.gz.tmp").exists());
+        assert_eq!(
+            read_to_string_lossy(&gz_path).unwrap(),
+            "first line\nsecond line\n"
+        );
+    }
+
+    #[test]
+    fn test_failed_compression_keeps_original() {
+        let dir = Path::new("log").join("CompressFailTest");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("CompressFailTest_20240508.log");
+        fs::write(&path, "kept line\n").unwrap();
+        // A directory in the way of the temporary archive makes the compression fail
+        fs::create_dir(dir.join("CompressFailTest_20240508.log.gz.tmp")).unwrap();
+
+        assert!(compress_file(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "kept line\n");
+        assert!(!dir.join("CompressFailTest_20240508.log.gz").exists());
+    }
+}
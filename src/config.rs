@@ -0,0 +1,443 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+#[cfg(feature = "color")]
+use colored::{Color, ColoredString, Colorize};
+use log::LevelFilter;
+use regex::Regex;
+
+use crate::duration::DurationFormat;
+use crate::event::{LogEvent, DEFAULT_TIME_FORMAT};
+use crate::log_level::LogLevel;
+use crate::log_util::{DEFAULT_SHOW_LOCATION, ENV_TARGET_LEVELS};
+use crate::style::{self, LevelLabel};
+
+/// Output settings of a `LogUtil`.
+///
+/// The running logger keeps its configuration behind an `ArcSwap`, so a changed configuration
+/// built from `LogUtil::config` can be applied in one step with `LogUtil::reconfigure`.
+/// Every record is formatted with a single snapshot, it never sees a half-applied change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    /// Color the console output, by default only when stdout is a terminal
+    pub color: bool,
+    /// Show the timestamp in the bracketed prefix
+    pub show_timestamp: bool,
+    /// chrono strftime format of the timestamp, set it with `LogUtil::try_set_timestamp_format`
+    /// to have it validated
+    pub timestamp_format: String,
+    /// Annotate records with the time elapsed since the previous record
+    pub show_delta: bool,
+    /// How the elapsed time of `show_delta` is rendered
+    pub delta_format: DurationFormat,
+    /// Placed between the bracketed prefix and the message
+    pub field_separator: String,
+    /// Minimum time between two written `output_progress_msg` updates, zero writes every update
+    pub progress_min_update_interval: Duration,
+    /// Rewrite every progress update in the dated file too, not just the final one
+    pub progress_to_date_file: bool,
+    /// Target prefixes whose records are only written to the files, never to the console
+    pub console_silenced_targets: Vec<String>,
+    /// Console colors of the `[target]` segment by target prefix
+    #[cfg(feature = "color")]
+    pub target_colors: Vec<(String, Color)>,
+    /// Size after which the dated file continues in a numbered part, `None` never rotates
+    pub max_file_bytes: Option<u64>,
+    /// Age in days after which dated log files are deleted at rollover, `None` keeps them
+    pub max_retained_days: Option<u32>,
+    /// Gzip the previous day's dated files at rollover
+    #[cfg(feature = "flate2")]
+    pub compress_rolled: bool,
+    /// Maximum number of time zone tree files open at once, `None` keeps all of them open.
+    /// The other files of the logger are not counted.
+    pub max_open_zone_files: Option<usize>,
+    /// Longest time written records stay buffered, `None` flushes the files after every record
+    pub flush_interval: Option<Duration>,
+    /// Print warnings and errors to stderr, the other levels to stdout
+    pub warnings_to_stderr: bool,
+    /// Also write every record to the file of its level, `<class_name>_<LEVEL>.log`
+    pub split_by_level: bool,
+    /// Write a run of identical records to the files once, followed by a
+    /// `...last message repeated N times` line
+    pub dedup: bool,
+    /// Most records a `capturing` logger keeps, the oldest are dropped above it
+    pub max_tracked_messages: usize,
+    /// Annotate records with the name of the logging thread, or its id when unnamed
+    pub show_thread: bool,
+    /// Start the records in the files with their module path, and the line for errors
+    pub show_source: bool,
+    /// Show the file and line of errors on the console, by default in debug builds only
+    pub show_location: bool,
+    /// Annotate records logged from inside a tokio task with the task id
+    #[cfg(feature = "tokio")]
+    pub show_task_id: bool,
+    /// Applied in order to every message before it is written anywhere
+    pub redactions: Vec<Redaction>,
+    /// Levels by target prefix overriding the logger's level, initially from `RUST_LOG`
+    pub target_levels: Vec<(String, LevelFilter)>,
+    /// Levels of the sinks that don't follow the logger's level
+    pub sink_levels: Vec<(Sink, LevelFilter)>,
+    /// Only records whose target contains this text pass, in addition to the levels
+    pub target_filter: Option<String>,
+    /// Padding of the level labels on the console and in the files
+    pub level_align: LevelAlign,
+    /// Full, three letter or one letter level labels on the console and in the files
+    pub level_label: LevelLabel,
+    /// Layout of the records in the log files
+    pub format: Format,
+    /// Time zone of the timestamps and of the date the files roll over on
+    pub time_zone: TimeZoneMode,
+    /// What the latest file `<class_name>.log` keeps, set it with `LogUtilBuilder::main_log_mode`
+    /// since the file is opened when the logger is built
+    pub main_log_mode: MainLogMode,
+    /// Whether the latest file `<class_name>.log` is written at all, set it with
+    /// `LogUtilBuilder::main_log`. Without it only the dated files are kept.
+    pub main_log: bool,
+    /// Print records to the console in the file format too when it is `Format::Json`
+    #[cfg(feature = "serde_json")]
+    pub json_console: bool,
+    /// Also write the records as JSON lines to `<class_name>.jsonl`
+    #[cfg(feature = "serde_json")]
+    pub json_file: bool,
+}
+
+/// Default `LogConfig::max_tracked_messages`
+pub const DEFAULT_MAX_TRACKED_MESSAGES: usize = 10_000;
+
+/// Time zone the timestamps are rendered in, which also decides the date in the file names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeZoneMode {
+    /// The local time zone of the machine
+    #[default]
+    Local,
+    Utc,
+    /// A fixed offset from UTC, e.g. `FixedOffset::east_opt(9 * 3600)` for JST
+    Fixed(FixedOffset),
+}
+
+impl TimeZoneMode {
+    /// `time` in this time zone
+    pub fn convert<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> DateTime<FixedOffset> {
+        match self {
+            TimeZoneMode::Local => time.with_timezone(&Local).fixed_offset(),
+            TimeZoneMode::Utc => time.with_timezone(&Utc).fixed_offset(),
+            TimeZoneMode::Fixed(offset) => time.with_timezone(offset),
+        }
+    }
+
+    /// The current time in this time zone
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.convert(&Utc::now())
+    }
+}
+
+/// Content of the latest file `<class_name>.log`. The dated file always appends and keeps
+/// every record of the day in any mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MainLogMode {
+    /// Emptied when the logger is created and at every rollover, so it holds the records of
+    /// the current process on the current day
+    #[default]
+    Truncate,
+    /// Never emptied, records of earlier runs and days are kept across restarts and rollovers
+    Append,
+    /// Only the latest record or progress update, e.g. for a status file watched by a script
+    LatestLine,
+}
+
+/// A destination of the records that can have a level of its own, see
+/// `LogUtil::set_sink_level`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sink {
+    /// The console
+    Console,
+    /// The log files, including the level files and time zone trees, or the writer of
+    /// `with_writer` and `with_tcp`
+    Files,
+    /// The daemon of `LogUtil::with_syslog`
+    #[cfg(all(unix, feature = "syslog"))]
+    Syslog,
+    /// The files of `LogUtil::start_capture` and the records of a `capturing` logger. They
+    /// are not affected by `LogUtil::silence_console_for_target`.
+    Capture,
+}
+
+impl Sink {
+    const ALL: &'static [Sink] = &[
+        Sink::Console,
+        Sink::Files,
+        #[cfg(all(unix, feature = "syslog"))]
+        Sink::Syslog,
+        Sink::Capture,
+    ];
+}
+
+/// Padding of the level label in the `[timestamp LEVEL]` prefix to the width of the longest
+/// label, so the messages of all levels start in the same column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LevelAlign {
+    /// No padding, `[... INFO] message`
+    #[default]
+    None,
+    /// Spaces after the label, `[... INFO ] message`
+    Left,
+    /// Spaces before the label, `[...  INFO] message`
+    Right,
+}
+
+/// Characters of `text` a terminal shows, without the ANSI color escapes
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in text.chars() {
+        match c {
+            '\u{1b}' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if !in_escape => width += 1,
+            _ => {}
+        }
+    }
+    width
+}
+
+/// Layout of the records written to the log files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// The standard `[timestamp LEVEL] message` line
+    #[default]
+    Text,
+    /// One JSON object per line, see `LogEvent::render_json`
+    #[cfg(feature = "serde_json")]
+    Json,
+}
+
+/// Replaces every match of `pattern` in a message with `replacement`, which may refer to
+/// capture groups like `Regex::replace_all`, e.g. `Bearer \w+` with `Bearer ***`
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    pub pattern: Regex,
+    pub replacement: String,
+}
+
+impl PartialEq for Redaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern.as_str() == other.pattern.as_str() && self.replacement == other.replacement
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            color: io::stdout().is_terminal(),
+            show_timestamp: true,
+            timestamp_format: String::from(DEFAULT_TIME_FORMAT),
+            show_delta: false,
+            delta_format: DurationFormat::Human,
+            field_separator: String::from(" "),
+            progress_min_update_interval: Duration::ZERO,
+            progress_to_date_file: false,
+            console_silenced_targets: Vec::new(),
+            #[cfg(feature = "color")]
+            target_colors: Vec::new(),
+            max_file_bytes: None,
+            max_retained_days: None,
+            #[cfg(feature = "flate2")]
+            compress_rolled: false,
+            max_open_zone_files: None,
+            split_by_level: false,
+            dedup: false,
+            max_tracked_messages: DEFAULT_MAX_TRACKED_MESSAGES,
+            show_thread: false,
+            show_source: false,
+            show_location: DEFAULT_SHOW_LOCATION,
+            warnings_to_stderr: false,
+            flush_interval: None,
+            redactions: Vec::new(),
+            target_levels: ENV_TARGET_LEVELS.clone(),
+            sink_levels: Vec::new(),
+            target_filter: None,
+            level_align: LevelAlign::None,
+            level_label: LevelLabel::Full,
+            format: Format::Text,
+            time_zone: TimeZoneMode::Local,
+            main_log_mode: MainLogMode::Truncate,
+            main_log: true,
+            #[cfg(feature = "serde_json")]
+            json_console: false,
+            #[cfg(feature = "serde_json")]
+            json_file: false,
+            #[cfg(feature = "tokio")]
+            show_task_id: false,
+        }
+    }
+}
+
+impl LogConfig {
+    pub(crate) fn is_console_silenced(&self, target: &str) -> bool {
+        self.console_silenced_targets
+            .iter()
+            .any(|prefix| target_matches(target, prefix))
+    }
+
+    /// Apply all redactions to `message`, borrowing it unchanged when nothing matches
+    pub(crate) fn redact<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        let mut message = Cow::Borrowed(message);
+        for redaction in &self.redactions {
+            if let Cow::Owned(redacted) = redaction
+                .pattern
+                .replace_all(&message, redaction.replacement.as_str())
+            {
+                message = Cow::Owned(redacted);
+            }
+        }
+        message
+    }
+
+    /// The level of the longest `target_levels` prefix matching `target`
+    pub(crate) fn target_level(&self, target: &str) -> Option<LevelFilter> {
+        self.target_levels
+            .iter()
+            .filter(|(prefix, _)| target_matches(target, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+    }
+
+    /// The level of `sink`, `level` (the logger's level for the record) unless the sink has
+    /// one of its own
+    pub(crate) fn sink_level(&self, sink: Sink, level: LevelFilter) -> LevelFilter {
+        self.sink_levels
+            .iter()
+            .find(|(s, _)| *s == sink)
+            .map_or(level, |(_, level)| *level)
+    }
+
+    /// The most verbose level of all sinks, records above it reach no sink
+    pub(crate) fn max_sink_level(&self, level: LevelFilter) -> LevelFilter {
+        Sink::ALL
+            .iter()
+            .map(|sink| self.sink_level(*sink, level))
+            .fold(LevelFilter::Off, Ord::max)
+    }
+
+    /// The colored `[target] ` console segment of the longest `target_colors` prefix matching
+    /// `target`, or `None` when no prefix matches
+    #[cfg(feature = "color")]
+    pub(crate) fn target_segment(&self, target: &str) -> Option<ColoredString> {
+        self.target_colors
+            .iter()
+            .filter(|(prefix, _)| target_matches(target, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, color)| format!("[{}] ", target).color(*color))
+    }
+
+    /// Render `time` with `timestamp_format`. A format that can't be rendered falls back to
+    /// `DEFAULT_TIME_FORMAT` instead of failing the record.
+    pub(crate) fn format_time<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let mut rendered = String::new();
+        if write!(rendered, "{}", time.format(&self.timestamp_format)).is_err() {
+            return time.format(DEFAULT_TIME_FORMAT).to_string();
+        }
+        rendered
+    }
+
+    pub(crate) fn console_to_stderr(&self, level: LogLevel) -> bool {
+        self.warnings_to_stderr && level >= LogLevel::Warn
+    }
+
+    /// Build the bracketed `[time LEVEL]` prefix of the files, followed by the field separator
+    pub(crate) fn format_prefix(
+        &self,
+        time: impl fmt::Display,
+        level: impl Into<LogLevel>,
+    ) -> String {
+        let label = style::level_text(level.into(), self.level_label);
+        self.format_prefix_with_label(time, label.to_string())
+    }
+
+    /// `format_prefix` with the label already rendered, e.g. colored for the console
+    pub(crate) fn format_prefix_with_label(
+        &self,
+        time: impl fmt::Display,
+        level: String,
+    ) -> String {
+        // The console label may be colored, the escapes take no room
+        let width = self.level_label.width();
+        let padding = " ".repeat(width.saturating_sub(visible_width(&level)));
+        let level = match self.level_align {
+            LevelAlign::None => level,
+            LevelAlign::Left => level + &padding,
+            LevelAlign::Right => padding + &level,
+        };
+        if self.show_timestamp {
+            format!("[{} {}]{}", time, level, self.field_separator)
+        } else {
+            format!("[{}]{}", level, self.field_separator)
+        }
+    }
+
+    /// Render the file line of `event` in the configured format, `body` being the message with
+    /// its annotations for the text format
+    pub(crate) fn format_file_line(&self, event: &LogEvent, time: &str, body: &str) -> String {
+        match self.format {
+            Format::Text => format!("{}{}\n", self.format_prefix(time, event.level), body),
+            #[cfg(feature = "serde_json")]
+            Format::Json => event.render_json(time) + "\n",
+        }
+    }
+}
+
+/// A timestamp format chrono can't render, returned by `LogUtil::try_set_timestamp_format`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    format: String,
+}
+
+impl FormatError {
+    /// The rejected format string
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid timestamp format: {:?}", self.format)
+    }
+}
+
+impl Error for FormatError {}
+
+/// Check that `format` only uses valid chrono specifiers and renders a non-empty timestamp
+pub fn validate_timestamp_format(format: &str) -> Result<(), FormatError> {
+    let error = || FormatError {
+        format: format.to_string(),
+    };
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(error());
+    }
+    let sample = FixedOffset::east_opt(0)
+        .unwrap()
+        .with_ymd_and_hms(2024, 1, 2, 3, 4, 5)
+        .unwrap();
+    let mut rendered = String::new();
+    match write!(rendered, "{}", sample.format(format)) {
+        Ok(()) if !rendered.trim().is_empty() => Ok(()),
+        _ => Err(error()),
+    }
+}
+
+/// Whether `target` is the module `prefix` itself or one of its submodules
+pub(crate) fn target_matches(target: &str, prefix: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
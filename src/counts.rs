@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::log_level::LogLevel;
+
+/// Number of records logged per level, returned by `LogUtil::counts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LevelCounts {
+    pub error: u64,
+    pub warn: u64,
+    pub info: u64,
+    pub debug: u64,
+    pub trace: u64,
+}
+
+impl LevelCounts {
+    /// Records of all levels
+    pub fn total(&self) -> u64 {
+        self.error + self.warn + self.info + self.debug + self.trace
+    }
+}
+
+/// The live counters behind `LevelCounts`, indexed by `LogLevel::severity`
+#[derive(Default)]
+pub(crate) struct LevelCounters([AtomicU64; 5]);
+
+impl LevelCounters {
+    pub(crate) fn increment(&self, level: LogLevel) {
+        self.0[level.severity() as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LevelCounts {
+        let count = |level: LogLevel| self.0[level.severity() as usize].load(Ordering::Relaxed);
+        LevelCounts {
+            error: count(LogLevel::Error),
+            warn: count(LogLevel::Warn),
+            info: count(LogLevel::Info),
+            debug: count(LogLevel::Debug),
+            trace: count(LogLevel::Trace),
+        }
+    }
+
+    /// Set all counters to zero, returning the counts until now
+    pub(crate) fn reset(&self) -> LevelCounts {
+        let take = |level: LogLevel| self.0[level.severity() as usize].swap(0, Ordering::Relaxed);
+        LevelCounts {
+            error: take(LogLevel::Error),
+            warn: take(LogLevel::Warn),
+            info: take(LogLevel::Info),
+            debug: take(LogLevel::Debug),
+            trace: take(LogLevel::Trace),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+/// How an elapsed time is rendered in a log message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurationFormat {
+    /// Seconds with millisecond precision, e.g. `63.000s`
+    #[default]
+    Human,
+    /// An ISO 8601 duration, e.g. `PT1M3S`, see `fmt_iso8601_duration`
+    Iso8601,
+}
+
+impl DurationFormat {
+    pub fn format(&self, duration: Duration) -> String {
+        match self {
+            DurationFormat::Human => format!("{:.3}s", duration.as_secs_f64()),
+            DurationFormat::Iso8601 => fmt_iso8601_duration(duration),
+        }
+    }
+}
+
+/// Format `duration` as an ISO 8601 duration such as `PT1M3S` or `PT0.25S`.
+/// Hours are not folded into days, since a day isn't always 24 hours long.
+pub fn fmt_iso8601_duration(duration: Duration) -> String {
+    let total_secs = duration.as_secs();
+    let (hours, minutes, secs) = (total_secs / 3600, total_secs / 60 % 60, total_secs % 60);
+    let nanos = duration.subsec_nanos();
+    let mut out = String::from("PT");
+    if hours > 0 {
+        out.push_str(&format!("{hours}H"));
+    }
+    if minutes > 0 {
+        out.push_str(&format!("{minutes}M"));
+    }
+    if nanos > 0 {
+        let fraction = format!("{nanos:09}");
+        out.push_str(&format!("{secs}.{}S", fraction.trim_end_matches('0')));
+    } else if secs > 0 || total_secs == 0 {
+        out.push_str(&format!("{secs}S"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fmt_iso8601_duration() {
+        assert_eq!(fmt_iso8601_duration(Duration::ZERO), "PT0S");
+        assert_eq!(fmt_iso8601_duration(Duration::from_millis(250)), "PT0.25S");
+        assert_eq!(fmt_iso8601_duration(Duration::from_secs(63)), "PT1M3S");
+        assert_eq!(
+            fmt_iso8601_duration(Duration::from_millis(63_500)),
+            "PT1M3.5S"
+        );
+        assert_eq!(fmt_iso8601_duration(Duration::from_secs(3600)), "PT1H");
+        assert_eq!(
+            fmt_iso8601_duration(Duration::from_secs(26 * 3600 + 5 * 60 + 7)),
+            "PT26H5M7S"
+        );
+        assert_eq!(
+            fmt_iso8601_duration(Duration::from_nanos(1)),
+            "PT0.000000001S"
+        );
+    }
+
+    #[test]
+    fn test_duration_format() {
+        let duration = Duration::from_millis(1500);
+        assert_eq!(DurationFormat::Human.format(duration), "1.500s");
+        assert_eq!(DurationFormat::Iso8601.format(duration), "PT1.5S");
+        assert_eq!(
+            DurationFormat::Human.format(Duration::from_millis(20)),
+            "0.020s"
+        );
+    }
+}
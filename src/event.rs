@@ -0,0 +1,217 @@
+use std::fmt;
+use std::thread;
+
+use chrono::{DateTime, Local};
+use log::kv::{self, VisitSource};
+use log::{Level, Record};
+
+/// Timestamp layout of the standard log line, with milliseconds to order records within a second
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Everything known about one log record, detached from the `log::Record` it was built from.
+///
+/// Sinks and formatters receive this instead of the raw record, so custom outputs see exactly
+/// the same fields as the built-in ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub module_path: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+    /// Name of the logging thread, or its id when the thread is unnamed
+    pub thread: String,
+    /// Structured key-values attached to the record, in order
+    pub kv: Vec<(String, String)>,
+    /// Names of the tracing spans the event was recorded in, outermost first, joined by `:`.
+    /// `None` for records of the `log` facade.
+    pub span: Option<String>,
+}
+
+impl LogEvent {
+    /// Capture `record` on the current thread, timestamped now
+    pub fn from_record(record: &Record) -> LogEvent {
+        LogEvent::from_record_at(record, Local::now())
+    }
+
+    /// Capture `record` on the current thread, timestamped `time`
+    pub fn from_record_at(record: &Record, time: DateTime<Local>) -> LogEvent {
+        let mut kv = KeyValues(Vec::new());
+        let _ = record.key_values().visit(&mut kv);
+        LogEvent {
+            time,
+            level: record.level(),
+            target: record.target().to_string(),
+            module_path: record.module_path().map(str::to_string),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+            message: record.args().to_string(),
+            thread: current_thread_name(),
+            kv: kv.0,
+            span: None,
+        }
+    }
+
+    /// Render the crate's standard log line, e.g. `[2024-05-08 12:24:05.123 INFO] message`
+    pub fn render_default(&self) -> String {
+        format!(
+            "[{} {}] {}",
+            self.time.format(DEFAULT_TIME_FORMAT),
+            self.level,
+            self.message
+        )
+    }
+
+    /// `[module::path] ` of the code that logged the event, or of its target when unknown.
+    /// Errors also show the line, e.g. `[app::db:42] `.
+    pub(crate) fn source_segment(&self) -> String {
+        let module_path = self.module_path.as_deref().unwrap_or(&self.target);
+        match (self.level, self.line) {
+            (Level::Error, Some(line)) => format!("[{module_path}:{line}] "),
+            _ => format!("[{module_path}] "),
+        }
+    }
+
+    /// The structured key-values as ` {key=value ...}`, appended to the message in the text
+    /// format. Empty without key-values.
+    pub fn format_fields(&self) -> String {
+        if self.kv.is_empty() {
+            return String::new();
+        }
+        let pairs: Vec<String> = self.kv.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        format!(" {{{}}}", pairs.join(" "))
+    }
+
+    /// Render the event as a single-line JSON object with the fields `timestamp`, `level`,
+    /// `target`, `module_path`, `line` and `message`. Absent fields are `null`. Structured
+    /// key-values are nested in a `fields` object, which is left out without key-values.
+    #[cfg(feature = "serde_json")]
+    pub fn render_json(&self, timestamp: &str) -> String {
+        let mut json = serde_json::json!({
+            "timestamp": timestamp,
+            "level": self.level.as_str(),
+            "target": self.target,
+            "module_path": self.module_path,
+            "line": self.line,
+            "message": self.message,
+        });
+        if !self.kv.is_empty() {
+            let fields: serde_json::Map<String, serde_json::Value> = self
+                .kv
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().into()))
+                .collect();
+            json["fields"] = fields.into();
+        }
+        json.to_string()
+    }
+}
+
+impl fmt::Display for LogEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render_default())
+    }
+}
+
+pub(crate) fn current_thread_name() -> String {
+    let current = thread::current();
+    match current.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", current.id()),
+    }
+}
+
+struct KeyValues(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn event(level: Level, message: &str) -> LogEvent {
+        LogEvent {
+            time: Local.with_ymd_and_hms(2024, 5, 8, 12, 24, 5).unwrap(),
+            level,
+            target: String::from("app::db"),
+            module_path: Some(String::from("app::db")),
+            file: Some(String::from("src/db.rs")),
+            line: Some(42),
+            message: message.to_string(),
+            thread: String::from("main"),
+            kv: Vec::new(),
+            span: None,
+        }
+    }
+
+    #[test]
+    fn test_render_default() {
+        assert_eq!(
+            event(Level::Info, "connected").render_default(),
+            "[2024-05-08 12:24:05.000 INFO] connected"
+        );
+        assert_eq!(
+            event(Level::Error, "query failed").to_string(),
+            "[2024-05-08 12:24:05.000 ERROR] query failed"
+        );
+    }
+
+    #[test]
+    fn test_from_record() {
+        let event = LogEvent::from_record(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("app::http")
+                .module_path(Some("app::http"))
+                .line(Some(7))
+                .args(format_args!("slow request {}", 3))
+                .build(),
+        );
+        assert_eq!(event.level, Level::Warn);
+        assert_eq!(event.target, "app::http");
+        assert_eq!(event.line, Some(7));
+        assert_eq!(event.message, "slow request 3");
+        assert!(event.render_default().ends_with(" WARN] slow request 3"));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_render_json() {
+        let line = event(Level::Info, "said \"hi\"").render_json("2024-05-08 12:24:05");
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["timestamp"], "2024-05-08 12:24:05");
+        assert_eq!(value["level"], "INFO");
+        assert_eq!(value["target"], "app::db");
+        assert_eq!(value["module_path"], "app::db");
+        assert_eq!(value["line"], 42);
+        assert_eq!(value["message"], "said \"hi\"");
+        assert!(value.get("fields").is_none());
+
+        let mut with_fields = event(Level::Info, "hello");
+        with_fields.kv = vec![(String::from("user_id"), String::from("42"))];
+        let value: serde_json::Value =
+            serde_json::from_str(&with_fields.render_json("now")).unwrap();
+        assert_eq!(value["fields"]["user_id"], "42");
+    }
+
+    #[test]
+    fn test_format_fields() {
+        let mut event = event(Level::Info, "hello");
+        assert_eq!(event.format_fields(), "");
+        event.kv = vec![
+            (String::from("user_id"), String::from("42")),
+            (String::from("request"), String::from("abc")),
+        ];
+        assert_eq!(event.format_fields(), " {user_id=42 request=abc}");
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// A background thread calling `beat` once per interval, or after each wait computed by
+/// `spawn_with`, until it is stopped
+pub(crate) struct Heartbeat {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Heartbeat {
+    pub(crate) fn spawn(interval: Duration, beat: impl Fn() + Send + 'static) -> Heartbeat {
+        Heartbeat::spawn_with(move || interval, beat)
+    }
+
+    /// Wait `next_wait()` before every beat, e.g. until the next midnight
+    pub(crate) fn spawn_with(
+        next_wait: impl Fn() -> Duration + Send + 'static,
+        beat: impl Fn() + Send + 'static,
+    ) -> Heartbeat {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(next_wait()) {
+                beat();
+            }
+        });
+        Heartbeat { stop, thread }
+    }
+
+    /// Stop the thread and wait for a beat in progress to finish
+    pub(crate) fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt::Write;
+
+const BYTES_PER_ROW: usize = 16;
+
+/// Format `bytes` like `hexdump -C`: an offset, 16 hex bytes split in two groups of 8 and the
+/// printable ASCII characters, one row per line.
+pub(crate) fn format_hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+        if row > 0 {
+            out.push('\n');
+        }
+        let _ = write!(out, "{:08x} ", row * BYTES_PER_ROW);
+        for i in 0..BYTES_PER_ROW {
+            if i % 8 == 0 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(b) => {
+                    let _ = write!(out, "{:02x} ", b);
+                }
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        out.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push('|');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_hexdump() {
+        let dump = format_hexdump(b"Hello, world!\n\x00\xffAB");
+        assert_eq!(
+            dump,
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|\n\
+             00000010  41 42                                             |AB|"
+        );
+        assert_eq!(format_hexdump(b""), "");
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Size of the blocks read backwards from the end of a log file
+const TAIL_CHUNK_SIZE: u64 = 8 * 1024;
+
+/// Read the last `n` lines of the file at `path`, oldest first.
+/// The file is read backwards in chunks, so only the tail is loaded into memory.
+/// Invalid UTF-8 is replaced with `U+FFFD`.
+pub(crate) fn read_last_lines(path: &Path, n: usize) -> io::Result<Vec<String>> {
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+    if is_compressed(path) {
+        // A compressed stream can't be read backwards, decode it keeping only the last lines
+        let mut lines = VecDeque::with_capacity(n);
+        for line in BufReader::new(open_log_reader(path)?).split(b'\n') {
+            if lines.len() == n {
+                lines.pop_front();
+            }
+            lines.push_back(String::from_utf8_lossy(&line?).into_owned());
+        }
+        return Ok(lines.into());
+    }
+    let mut file = File::open(path)?;
+    let mut pos = file.seek(SeekFrom::End(0))?;
+    let mut buf: Vec<u8> = Vec::new();
+    let mut newlines = 0;
+    // Stop once the buffer holds more line breaks than requested lines,
+    // the first (possibly partial) line is then dropped below
+    while pos > 0 && newlines <= n {
+        let len = TAIL_CHUNK_SIZE.min(pos);
+        pos -= len;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = vec![0; len as usize];
+        file.read_exact(&mut chunk)?;
+        newlines += chunk.iter().filter(|b| **b == b'\n').count();
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+    let text = String::from_utf8_lossy(&buf);
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.len().saturating_sub(n);
+    Ok(lines[start..].iter().map(|line| line.to_string()).collect())
+}
+
+/// Read the whole file at `path`, replacing invalid UTF-8 with `U+FFFD`.
+pub(crate) fn read_to_string_lossy(path: &Path) -> io::Result<String> {
+    let mut buf = Vec::new();
+    open_log_reader(path)?.read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+pub(crate) fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Open a log file for reading, transparently decompressing `.gz` files.
+/// Without the `flate2` feature compressed files can't be read and an `Unsupported` error
+/// is returned.
+pub(crate) fn open_log_reader(path: &Path) -> io::Result<Box<dyn Read>> {
+    let file = File::open(path)?;
+    if !is_compressed(path) {
+        return Ok(Box::new(file));
+    }
+    #[cfg(feature = "flate2")]
+    {
+        Ok(Box::new(flate2::read::GzDecoder::new(file)))
+    }
+    #[cfg(not(feature = "flate2"))]
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Reading {} requires the `flate2` feature.", path.display()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_read_last_lines_across_chunks() {
+        let dir = Path::new("log").join("HistoryTest");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tail.log");
+        let mut file = File::create(&path).unwrap();
+        for i in 0..5000 {
+            writeln!(file, "line {i}").unwrap();
+        }
+
+        let lines = read_last_lines(&path, 3).unwrap();
+        assert_eq!(lines, vec!["line 4997", "line 4998", "line 4999"]);
+        assert_eq!(read_last_lines(&path, 6000).unwrap().len(), 5000);
+        assert!(read_last_lines(&path, 0).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_read_compressed_log() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let dir = Path::new("log").join("HistoryTest");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("compressed.log.gz");
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        for i in 0..100 {
+            writeln!(encoder, "line {i}").unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let content = read_to_string_lossy(&path).unwrap();
+        assert_eq!(content.lines().count(), 100);
+        assert!(content.starts_with("line 0\nline 1\n"));
+        assert_eq!(
+            read_last_lines(&path, 2).unwrap(),
+            vec!["line 98", "line 99"]
+        );
+    }
+}
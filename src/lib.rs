@@ -1,5 +1,35 @@
+mod background;
+pub mod builder;
+pub mod capture;
+pub mod clock;
+#[cfg(feature = "flate2")]
+mod compress;
+pub mod config;
+pub mod counts;
+pub mod duration;
+pub mod event;
+mod heartbeat;
+mod hexdump;
+mod history;
 pub mod log_level;
 pub mod log_util;
+pub mod progress;
+mod recent;
+mod sink;
+pub mod style;
+#[cfg(all(unix, feature = "syslog"))]
+pub mod syslog;
+pub mod tcp;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+pub mod version;
+mod zone_tree;
+
+// Reached by the exported macros, so callers don't need the dependency themselves
+#[doc(hidden)]
+pub use chrono;
+#[doc(hidden)]
+pub use log;
 
 #[cfg(test)]
 mod tests {
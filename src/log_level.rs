@@ -1,24 +1,213 @@
+use std::cmp::Ordering;
+use std::error::Error;
 use std::fmt;
 
-#[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Copy, Clone)]
+use log::{Level, LevelFilter};
+
+/// Level of a record in the `output_*_log` macros.
+///
+/// The discriminants are the historical values of this enum and do not reflect severity,
+/// use `severity` to compare levels. `Ord` follows `severity`, so `Error` is the greatest level.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum LogLevel {
+    Trace = 5,
     Debug = 4,
     Warn = 3,
     Info = 2,
     Error = 1,
 }
 
+impl LogLevel {
+    /// Conventional severity of the level, higher is more severe:
+    /// `Trace` = 0, `Debug` = 1, `Info` = 2, `Warn` = 3, `Error` = 4
+    pub fn severity(&self) -> u8 {
+        match self {
+            LogLevel::Trace => 0,
+            LogLevel::Debug => 1,
+            LogLevel::Info => 2,
+            LogLevel::Warn => 3,
+            LogLevel::Error => 4,
+        }
+    }
+
+    /// Verbosity on the scale of `log::Level`, higher is more verbose:
+    /// `Error` = 1, `Warn` = 2, `Info` = 3, `Debug` = 4, `Trace` = 5
+    pub fn verbosity(&self) -> u8 {
+        5 - self.severity()
+    }
+
+    /// Whether records of this level pass `filter`, the same test as for a `log::Level`.
+    /// Compare like this instead of casting the discriminant, which is on another scale.
+    pub fn is_enabled(&self, filter: LevelFilter) -> bool {
+        Level::from(*self) <= filter
+    }
+}
+
+impl PartialOrd for LogLevel {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LogLevel {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.severity().cmp(&other.severity())
+    }
+}
+
+/// The label of the console and the files, e.g. `WARN`. Honors width and alignment like
+/// `log::Level`, e.g. `{:<5}`.
 impl fmt::Display for LogLevel {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                LogLevel::Info => "INFO",
-                LogLevel::Debug => "DEBUG",
-                LogLevel::Error => "ERROR",
-                LogLevel::Warn => "WARNING",
+        f.pad(match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+        })
+    }
+}
+
+impl From<Level> for LogLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => LogLevel::Error,
+            Level::Warn => LogLevel::Warn,
+            Level::Info => LogLevel::Info,
+            Level::Debug => LogLevel::Debug,
+            Level::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+impl From<LogLevel> for Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Level::Error,
+            LogLevel::Warn => Level::Warn,
+            LogLevel::Info => Level::Info,
+            LogLevel::Debug => Level::Debug,
+            LogLevel::Trace => Level::Trace,
+        }
+    }
+}
+
+/// Parses the `Display` output of a level, e.g. `"INFO"` or `"WARN"`. `"WARNING"`, the label
+/// of earlier versions, is accepted too.
+impl TryFrom<&str> for LogLevel {
+    type Error = ParseLogLevelError;
+
+    fn try_from(name: &str) -> Result<Self, ParseLogLevelError> {
+        match name {
+            "TRACE" => Ok(LogLevel::Trace),
+            "DEBUG" => Ok(LogLevel::Debug),
+            "INFO" => Ok(LogLevel::Info),
+            "WARN" | "WARNING" => Ok(LogLevel::Warn),
+            "ERROR" => Ok(LogLevel::Error),
+            _ => Err(ParseLogLevelError {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+/// A string that is not the name of a `LogLevel`, returned by `LogLevel::try_from`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLogLevelError {
+    name: String,
+}
+
+impl ParseLogLevelError {
+    /// The string that failed to parse
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for ParseLogLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown log level {:?}", self.name)
+    }
+}
+
+impl Error for ParseLogLevelError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity_order() {
+        let mut levels = vec![
+            LogLevel::Error,
+            LogLevel::Debug,
+            LogLevel::Warn,
+            LogLevel::Info,
+        ];
+        levels.sort();
+        assert_eq!(
+            levels,
+            [
+                LogLevel::Debug,
+                LogLevel::Info,
+                LogLevel::Warn,
+                LogLevel::Error
+            ]
+        );
+        assert_eq!(levels.iter().max(), Some(&LogLevel::Error));
+        assert!(LogLevel::Error.severity() > LogLevel::Warn.severity());
+    }
+
+    #[test]
+    fn test_filtering_matches_log_level() {
+        let levels = [
+            LogLevel::Error,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+            LogLevel::Trace,
+        ];
+        let filters = [
+            LevelFilter::Off,
+            LevelFilter::Error,
+            LevelFilter::Warn,
+            LevelFilter::Info,
+            LevelFilter::Debug,
+            LevelFilter::Trace,
+        ];
+        for level in levels {
+            assert_eq!(level.verbosity() as usize, Level::from(level) as usize);
+            for filter in filters {
+                assert_eq!(level.is_enabled(filter), Level::from(level) <= filter);
             }
-        )
+        }
+        assert!(LogLevel::Warn.is_enabled(LevelFilter::Warn));
+        assert!(!LogLevel::Info.is_enabled(LevelFilter::Warn));
+        assert!(LogLevel::Error.is_enabled(LevelFilter::Warn));
+        assert!(!LogLevel::Error.is_enabled(LevelFilter::Off));
+    }
+
+    #[test]
+    fn test_conversions_round_trip() {
+        for level in [
+            LogLevel::Trace,
+            LogLevel::Debug,
+            LogLevel::Info,
+            LogLevel::Warn,
+            LogLevel::Error,
+        ] {
+            assert_eq!(LogLevel::from(Level::from(level)), level);
+            assert_eq!(LogLevel::try_from(level.to_string().as_str()), Ok(level));
+        }
+        assert_eq!(Level::from(LogLevel::Warn), Level::Warn);
+        assert_eq!(LogLevel::Warn.to_string(), Level::Warn.to_string());
+        assert_eq!(format!("[{:>5}]", LogLevel::Info), "[ INFO]");
+        assert_eq!(LogLevel::try_from("WARNING"), Ok(LogLevel::Warn));
+        assert_eq!(LogLevel::from(Level::Trace), LogLevel::Trace);
+        let error = LogLevel::try_from("VERBOSE").unwrap_err();
+        assert_eq!(error.name(), "VERBOSE");
+        assert_eq!(error.to_string(), "unknown log level \"VERBOSE\"");
+        assert!(LogLevel::try_from("info").is_err());
     }
 }
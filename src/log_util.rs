@@ -1,391 +1,1211 @@
-use std::fs::{File, OpenOptions};
-use std::io::{Seek, Write};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
+#[cfg(feature = "color")]
+use std::io::IsTerminal;
+use std::io::{BufWriter, Seek, Write};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::{fs, io};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use std::{fmt, fs, io, panic};
 
-use chrono::NaiveDate;
-use colored::Colorize;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveTime, SecondsFormat, Utc};
+#[cfg(feature = "color")]
+use colored::{Color, Colorize};
 use lazy_static::lazy_static;
 use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use regex::Regex;
 
+use crate::background::BackgroundWriter;
+use crate::builder::LogUtilBuilder;
+use crate::capture::{CaptureHandle, CaptureSinks};
+use crate::clock::Clock;
+#[cfg(feature = "flate2")]
+use crate::compress;
+use crate::config::{
+    self, Format, FormatError, LevelAlign, LogConfig, MainLogMode, Redaction, Sink, TimeZoneMode,
+};
+use crate::counts::{LevelCounters, LevelCounts};
+use crate::duration::DurationFormat;
+use crate::event::LogEvent;
+use crate::heartbeat::Heartbeat;
+use crate::hexdump::format_hexdump;
+use crate::history;
 use crate::log_level::LogLevel;
+use crate::progress::ProgressHandle;
+use crate::recent::RecentRecords;
+use crate::sink::LogSink;
+#[cfg(feature = "color")]
+use crate::style::ColorScheme;
+use crate::style::{self, LevelLabel};
+#[cfg(all(unix, feature = "syslog"))]
+use crate::syslog::{SyslogOptions, SyslogSink};
+use crate::tcp::{TcpOptions, TcpSender};
+use crate::version::{IS_RELEASE_BUILD, VERSION_INFO};
+use crate::zone_tree::{self, ZoneTree};
 #[macro_export]
 macro_rules! output_ln {
     ($($arg:tt)*) => {{
-        println!($($arg)*)
+        $crate::log_util::write_console(false, format_args!($($arg)*), true)
     }};
 }
 
 #[macro_export]
 macro_rules! output {
     ($($arg:tt)*) => {{
-        print!($($arg)*)
+        $crate::log_util::write_console(false, format_args!($($arg)*), false)
+    }};
+}
+
+#[macro_export]
+macro_rules! output_trace_log_ln {
+    ($time:expr, $($arg:tt)*) => {{
+        $crate::output_ln!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Trace), $crate::style::message($crate::log_level::LogLevel::Trace, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_debug_log_ln {
     ($time:expr, $($arg:tt)*) => {{
-        output_ln!("[{} {}] {}", $time, "DEBUG".to_string().bright_black(), format!($($arg)*).bright_black().underline())
+        $crate::output_ln!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Debug), $crate::style::message($crate::log_level::LogLevel::Debug, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_info_log_ln {
     ($time:expr, $($arg:tt)*) => {{
-        output_ln!("[{} {}] {}", $time, "INFO".to_string(), format!($($arg)*))
+        $crate::output_ln!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Info), $crate::style::message($crate::log_level::LogLevel::Info, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_warn_log_ln {
+    // Write to stderr instead of stdout
+    (@stderr $time:expr, $($arg:tt)*) => {{
+        $crate::log_util::write_console(true, format_args!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Warn), $crate::style::message($crate::log_level::LogLevel::Warn, &format!($($arg)*))), true)
+    }};
     ($time:expr, $($arg:tt)*) => {{
-        output_ln!("[{} {}] {}", $time, "WARN".to_string().yellow(), format!($($arg)*).yellow())
+        $crate::output_ln!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Warn), $crate::style::message($crate::log_level::LogLevel::Warn, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_error_log_ln {
+    // Write to stderr instead of stdout
+    (@stderr $time:expr, $($arg:tt)*) => {{
+        $crate::log_util::write_console(true, format_args!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Error), $crate::style::message($crate::log_level::LogLevel::Error, &format!($($arg)*))), true)
+    }};
+    ($time:expr, $($arg:tt)*) => {{
+        $crate::output_ln!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Error), $crate::style::message($crate::log_level::LogLevel::Error, &format!($($arg)*)))
+    }};
+}
+
+#[macro_export]
+macro_rules! output_trace_log {
     ($time:expr, $($arg:tt)*) => {{
-        output_ln!("[{} {}] {}", $time, "ERROR".to_string().red().bold(), format!($($arg)*).red().bold())
+        $crate::output!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Trace), $crate::style::message($crate::log_level::LogLevel::Trace, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_debug_log {
     ($time:expr, $($arg:tt)*) => {{
-        $crate::output!("[{} {}] {}", $time, "DEBUG".to_string().bright_black(), format!($($arg)*).bright_black().underline())
+        $crate::output!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Debug), $crate::style::message($crate::log_level::LogLevel::Debug, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_info_log {
     ($time:expr, $($arg:tt)*) => {{
-        $crate::output!("[{} {}] {}", $time, "INFO".to_string(), format!($($arg)*))
+        $crate::output!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Info), $crate::style::message($crate::log_level::LogLevel::Info, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_warn_log {
+    // Write to stderr instead of stdout
+    (@stderr $time:expr, $($arg:tt)*) => {{
+        $crate::log_util::write_console(true, format_args!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Warn), $crate::style::message($crate::log_level::LogLevel::Warn, &format!($($arg)*))), false)
+    }};
     ($time:expr, $($arg:tt)*) => {{
-        $crate::output!("[{} {}] {}", $time, "WARN".to_string().yellow(), format!($($arg)*).yellow())
+        $crate::output!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Warn), $crate::style::message($crate::log_level::LogLevel::Warn, &format!($($arg)*)))
     }};
 }
 
 #[macro_export]
 macro_rules! output_error_log {
+    // Write to stderr instead of stdout
+    (@stderr $time:expr, $($arg:tt)*) => {{
+        $crate::log_util::write_console(true, format_args!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Error), $crate::style::message($crate::log_level::LogLevel::Error, &format!($($arg)*))), false)
+    }};
     ($time:expr, $($arg:tt)*) => {{
-        output!("[{} {}] {}", $time, "ERROR".to_string().red().bold(), format!($($arg)*).red().bold())
+        $crate::output!("[{} {}] {}", $time, $crate::style::label($crate::log_level::LogLevel::Error), $crate::style::message($crate::log_level::LogLevel::Error, &format!($($arg)*)))
+    }};
+}
+
+/// Log a message through the `log` facade only the first time this call site is reached.
+/// Later executions of the same call site are skipped for the rest of the program.
+///
+/// ```
+/// for _ in 0..3 {
+///     log_util::log_once!(log::Level::Warn, "deprecated option, use --level");
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_once {
+    ($level:expr, $($arg:tt)+) => {{
+        static LOGGED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if !LOGGED.swap(true, std::sync::atomic::Ordering::Relaxed) {
+            $crate::log::log!($level, $($arg)+);
+        }
     }};
 }
 
+/// Format the current local time, with the default timestamp layout or the given chrono format
 #[macro_export]
 macro_rules! get_now_time_str {
     () => {{
+        $crate::get_now_time_str!($crate::event::DEFAULT_TIME_FORMAT)
+    }};
+    ($format:expr) => {{
         // Get current time
-        let now = chrono::Local::now();
-        now.format("%Y-%m-%d %H:%M:%S")
+        let now = $crate::chrono::Local::now();
+        now.format($format)
     }};
 }
 
+/// Print a colored console line for `$level`, a `LogLevel` or a `log::Level`. `@time` passes an
+/// already formatted timestamp, e.g. of the record being logged, instead of reading the clock
+/// again.
+///
+/// ```
+/// use log_util::log_level::LogLevel;
+///
+/// log_util::output_log!(LogLevel::Warn, "disk {}% full", 91);
+/// log_util::output_log_ln!(log::Level::Info, " and rising");
+/// let now = log_util::get_now_time_str!("%H:%M:%S");
+/// log_util::output_log_ln!(@time now, log::Level::Error, "given time");
+/// ```
 #[macro_export]
 macro_rules! output_log {
-    // Match the log level and any number of other arguments
-    ($level:expr, $($arg:tt)*) => {{
-        let now_str = $crate::get_now_time_str!();
-        // Judge the output format based on the log level
-        match $level {
-            LogLevel::Error => $crate::output_error_log!(now_str, $($arg)*),
-            LogLevel::Warn => $crate::output_warn_log!(now_str, $($arg)*),
-            LogLevel::Debug => $crate::output_debug_log!(now_str, $($arg)*),
+    (@time $time:expr, $level:expr, $($arg:tt)*) => {{
+        let now_str = $time;
+        // Judge the output format based on the log level, a `log::Level` converts
+        match $crate::log_level::LogLevel::from($level) {
+            $crate::log_level::LogLevel::Error => $crate::output_error_log!(now_str, $($arg)*),
+            $crate::log_level::LogLevel::Warn => $crate::output_warn_log!(now_str, $($arg)*),
+            $crate::log_level::LogLevel::Debug => $crate::output_debug_log!(now_str, $($arg)*),
+            $crate::log_level::LogLevel::Trace => $crate::output_trace_log!(now_str, $($arg)*),
             _ => $crate::output_info_log!(now_str, $($arg)*),
         }
     }};
+    // Match the log level and any number of other arguments
+    ($level:expr, $($arg:tt)*) => {{
+        $crate::output_log!(@time $crate::get_now_time_str!(), $level, $($arg)*)
+    }};
 }
 
+/// Like `output_log!`, ending the line
 #[macro_export]
 macro_rules! output_log_ln {
-    // Match the log level and any number of other arguments
-    ($level:expr, $($arg:tt)*) => {{
-        let now_str = get_now_time_str!();
-        // Judge the output format based on the log level
-        match $level {
-            LogLevel::Error => $crate::output_error_log_ln!(now_str, $($arg)*),
-            LogLevel::Warn => $crate::output_warn_log_ln!(now_str, $($arg)*),
-            LogLevel::Debug => $crate::output_debug_log_ln!(now_str, $($arg)*),
+    (@time $time:expr, $level:expr, $($arg:tt)*) => {{
+        let now_str = $time;
+        // Judge the output format based on the log level, a `log::Level` converts
+        match $crate::log_level::LogLevel::from($level) {
+            $crate::log_level::LogLevel::Error => $crate::output_error_log_ln!(now_str, $($arg)*),
+            $crate::log_level::LogLevel::Warn => $crate::output_warn_log_ln!(now_str, $($arg)*),
+            $crate::log_level::LogLevel::Debug => $crate::output_debug_log_ln!(now_str, $($arg)*),
+            $crate::log_level::LogLevel::Trace => $crate::output_trace_log_ln!(now_str, $($arg)*),
             _ => $crate::output_info_log_ln!(now_str, $($arg)*),
         }
     }};
+    // Match the log level and any number of other arguments
+    ($level:expr, $($arg:tt)*) => {{
+        $crate::output_log_ln!(@time $crate::get_now_time_str!(), $level, $($arg)*)
+    }};
 }
 
 lazy_static! {
-    static ref LOGGER: LogUtil = LogUtil {
-        class_name: "",
-        out_log_file: None,
-        out_log_file_line_position: None,
-        out_log_date_file: None,
-        out_log_date_file_line_position: None,
-        init_date: NaiveDate::default(),
-        out_log_date: Arc::new(Mutex::new(NaiveDate::default()))
-    };
+    static ref LOGGER: LogUtil = LogUtil::console_only();
     pub static ref MAX_LOG_LEVEL: LevelFilter = fetch_max_level_from_env();
+    /// The `target=level` directives of `RUST_LOG`, the initial `LogConfig::target_levels`
+    pub(crate) static ref ENV_TARGET_LEVELS: Vec<(String, LevelFilter)> =
+        parse_level_directives(&std::env::var("RUST_LOG").unwrap_or_default()).1;
+}
+
+/// The last record written to the files and how often it was repeated since, see
+/// `LogUtil::set_dedup`
+#[derive(Clone)]
+struct RepeatedRecord {
+    event: LogEvent,
+    message: String,
+    repeated: u64,
 }
 
 pub struct LogUtil {
     class_name: &'static str,
-    out_log_file: Option<Arc<Mutex<File>>>,
+    out_log_file: Option<Arc<Mutex<LogSink>>>,
     out_log_file_line_position: Option<Arc<Mutex<u64>>>,
-    out_log_date_file: Option<Arc<Mutex<File>>>,
+    out_log_date_file: Option<Arc<Mutex<LogSink>>>,
     out_log_date_file_line_position: Option<Arc<Mutex<u64>>>,
     out_log_date: Arc<Mutex<NaiveDate>>,
-    init_date: NaiveDate,
+    /// Numbered part of the dated file after size rotations, 0 for `<class_name>_YYYYMMDD.log`
+    out_log_date_file_index: Arc<AtomicU32>,
+    /// The files of `split_by_level` by level, each opened with the first record of its level
+    level_files: Arc<Mutex<HashMap<Level, LogSink>>>,
+    /// The `<class_name>.jsonl` file of `set_json_file`, opened with its first record
+    #[cfg(feature = "serde_json")]
+    json_file: Arc<Mutex<Option<LogSink>>>,
+    /// The last record written to the files while `dedup` is on
+    last_record: Arc<Mutex<Option<RepeatedRecord>>>,
+    /// Level override of this logger, `LEVEL_INHERIT` when it follows its parent
+    level: AtomicUsize,
+    parent: Option<&'static LogUtil>,
+    config: ArcSwap<LogConfig>,
+    /// Directory of the log files, `<root>/<class_name>/` unless `LogUtilBuilder::log_dir`
+    /// names another one
+    log_dir: PathBuf,
+    /// The file of a `single_file` logger, which is not under `log_dir`
+    single_file_path: Option<PathBuf>,
+    dir_mode: u32,
+    /// When the previous record was logged, shared by all threads
+    last_record_time: Mutex<Option<Instant>>,
+    /// When `output_progress_msg` last wrote an update
+    last_progress_render: Mutex<Option<Instant>>,
+    /// The latest progress update dropped by the throttle, written on `flush()`
+    pending_progress: Mutex<Option<(LogLevel, String)>>,
+    /// Characters of the progress line on the console, blanked when a shorter update follows
+    last_progress_width: Mutex<usize>,
+    captures: Arc<CaptureSinks>,
+    /// Level and message of every record of a `capturing` logger
+    captured: Option<CapturedRecords>,
+    /// Extra dated file trees, each rolling over in its own time zone
+    zone_trees: Arc<Mutex<Vec<ZoneTree>>>,
+    record_callbacks: Arc<ArcSwap<Vec<RecordCallback>>>,
+    /// Set with `set_formatter`, renders the console and file lines instead of the config
+    formatter: Arc<ArcSwapOption<RecordFormatter>>,
+    /// Set with `set_redactor`, runs on every message after the redaction patterns
+    redactor: Arc<ArcSwapOption<Redactor>>,
+    /// Records per level that passed the level filter, shared with child loggers
+    counts: Arc<LevelCounters>,
+    /// The last formatted records for `recent`, shared with child loggers
+    recent: Arc<RecentRecords>,
+    /// Set after `MAX_FILE_WRITE_FAILURES` failed writes in a row, see `recover_files`
+    files_suspended: AtomicBool,
+    file_write_failures: AtomicUsize,
+    heartbeat: Mutex<Option<Heartbeat>>,
+    /// The thread started by `start_rollover_timer`
+    rollover_timer: Mutex<Option<Heartbeat>>,
+    /// The syslog socket of a `with_syslog` logger
+    #[cfg(all(unix, feature = "syslog"))]
+    syslog: Option<Arc<SyslogSink>>,
+    /// The writer thread of a `new_async` logger
+    background: Option<BackgroundWriter>,
+    /// When the buffered file writes were last flushed for `flush_interval`
+    last_flush: Mutex<Instant>,
+    /// Time of the records, the progress updates and the rollover
+    clock: Arc<dyn Clock>,
+}
+
+/// A user function invoked with every record that passes the level filter
+pub type RecordCallback = Arc<dyn Fn(&LogEvent) + Send + Sync>;
+
+/// A user function rendering the whole line of a record, without the line break
+pub type RecordFormatter = Box<dyn Fn(&LogEvent) -> String + Send + Sync>;
+
+/// A user function scrubbing a message, returning it borrowed when nothing changed
+pub type Redactor = Box<dyn Fn(&str) -> Cow<str> + Send + Sync>;
+
+type CapturedRecords = Arc<Mutex<VecDeque<(LogLevel, String)>>>;
+
+/// Consecutive failed file writes after which file output is suspended
+const MAX_FILE_WRITE_FAILURES: usize = 3;
+
+/// How long after midnight the rollover timer wakes up
+const ROLLOVER_TIMER_DELAY: Duration = Duration::from_millis(100);
+
+/// Longest sleep of the rollover timer, so a changed system clock or a suspend delays the
+/// rollover by at most this long
+const ROLLOVER_TIMER_MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// Marker stored in `LogUtil::level` when no override has been set
+const LEVEL_INHERIT: usize = usize::MAX;
+
+/// Max level set with `LogUtil::set_max_level`, `LEVEL_INHERIT` until then
+static RUNTIME_MAX_LEVEL: AtomicUsize = AtomicUsize::new(LEVEL_INHERIT);
+
+/// Highest level given to `set_target_level` or `set_sink_level` of any logger, which
+/// `set_max_level` never lowers the `log` max level below
+static CONFIG_MAX_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Off as usize);
+
+/// Let the `log` macros pass records up to `level`, which a target or sink level allows
+fn raise_facade_level(level: LevelFilter) {
+    CONFIG_MAX_LEVEL.fetch_max(level as usize, Ordering::Relaxed);
+    if level > log::max_level() {
+        log::set_max_level(level);
+    }
+}
+
+fn level_filter_from_usize(level: usize) -> LevelFilter {
+    match level {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
 }
 
 impl LogUtil {
+    /// Log a record of `level` through this logger without the `log` macros, e.g. with a
+    /// level chosen at runtime: `logger.log_at(level, format_args!("{} retries", n))`. It is
+    /// filtered and written like a record of `Log::log` with the class name as its target,
+    /// the location is the caller's.
+    #[track_caller]
+    pub fn log_at(&self, level: LogLevel, args: fmt::Arguments) {
+        let location = panic::Location::caller();
+        self.log(
+            &Record::builder()
+                .level(level.into())
+                .target(self.class_name)
+                .file(Some(location.file()))
+                .line(Some(location.line()))
+                .args(args)
+                .build(),
+        );
+    }
+
+    /// `log_at` with a plain message
+    #[track_caller]
+    pub fn log_msg(&self, level: LogLevel, msg: &str) {
+        self.log_at(level, format_args!("{}", msg));
+    }
+
+    /// Log the version and build of log_util and the start time as one info line, e.g.
+    /// `log_util 0.1.0 (commit 1a2b3c4, release build on 2024-05-08) started at
+    /// 2024-05-09T08:00:00.000+02:00`, which helps to match a support case to a build
+    pub fn log_startup_banner(&self) {
+        let started = self
+            .clock
+            .now()
+            .to_rfc3339_opts(SecondsFormat::Millis, false);
+        self.log_at(
+            LogLevel::Info,
+            format_args!("{} started at {}", VERSION_INFO, started),
+        );
+    }
+
+    /// Start a progress line of `level`, e.g. for a download. Prefer it over calling
+    /// `output_progress_msg` directly, the handle writes the final update when dropped.
+    pub fn start_progress(&self, level: LogLevel) -> ProgressHandle<'_> {
+        ProgressHandle::new(self, level)
+    }
+
+    /// Rewrite the current progress line with `msg`, on the console and in the latest file. The
+    /// last update of a progress passes `is_process_stop`, which ends the line. The dated file
+    /// only gets the final update, see `set_progress_to_date_file`.
     pub fn output_progress_msg(&self, log_level: LogLevel, msg: &str, is_process_stop: bool) {
-        if log_level as u32 <= *MAX_LOG_LEVEL as u32 {
-            let now = chrono::Local::now();
-            let now_str = now.format("%Y-%m-%d %H:%M:%S");
-            let now_date_str = now.format("%Y%m%d");
-            output!("\r");
-            match log_level {
-                LogLevel::Debug => output_debug_log!(now_str, "{}", msg),
-                LogLevel::Error => {
-                    output_error_log!(now_str, "{}", msg)
+        let config = self.config.load();
+        let (to_console, to_files) = self.progress_sinks(&config, log_level);
+        // Same filter as `enabled`
+        if to_console || to_files {
+            self.counts.increment(log_level);
+            if self.progress_render_due(&config, log_level, msg, is_process_stop) {
+                self.write_progress(&config, log_level, msg, is_process_stop);
+            }
+        }
+    }
+
+    /// Whether the console and the files take progress updates of `log_level`
+    fn progress_sinks(&self, config: &LogConfig, log_level: LogLevel) -> (bool, bool) {
+        let level = self.level();
+        (
+            log_level.is_enabled(config.sink_level(Sink::Console, level)),
+            log_level.is_enabled(config.sink_level(Sink::Files, level)),
+        )
+    }
+
+    /// Write a progress update that is due to the console and the files
+    fn write_progress(
+        &self,
+        config: &LogConfig,
+        log_level: LogLevel,
+        msg: &str,
+        is_process_stop: bool,
+    ) {
+        let (to_console, to_files) = self.progress_sinks(config, log_level);
+        let msg = self.redact(config, msg);
+        let now = config.time_zone.convert(&self.clock.now());
+        let now_str = config.format_time(&now);
+        if to_console {
+            self.output_progress_console(config, &now_str, log_level, &msg, is_process_stop);
+        }
+        if !to_files {
+            return;
+        }
+        // Held until both files are written, so the update goes to the files of one day
+        let (out_log_date_locked, rolled) = self.ensure_current_day(config, &now);
+        if self.track_file_failure(rolled) {
+            return;
+        }
+        // Write normally to the log of the current day
+        if let (Some(write_file), Some(line_position)) = (
+            self.out_log_file.as_ref(),
+            self.out_log_file_line_position.as_ref(),
+        ) {
+            let mut write_file = write_file.lock().unwrap();
+            let line = format!("{}{}", config.format_prefix(&now_str, log_level), msg);
+            let mut lp = line_position.lock().unwrap();
+            write_progress_line(&mut write_file, &mut lp, &line, is_process_stop);
+        }
+        // The dated file keeps the history, only the final update goes there unless asked
+        if let (Some(write_file), Some(line_position), true) = (
+            self.out_log_date_file.as_ref(),
+            self.out_log_date_file_line_position.as_ref(),
+            config.progress_to_date_file || is_process_stop,
+        ) {
+            let mut write_file = write_file.lock().unwrap();
+            let line = format!("{}{}", config.format_prefix(&now_str, log_level), msg);
+            let mut lp = line_position.lock().unwrap();
+            write_progress_line(&mut write_file, &mut lp, &line, is_process_stop);
+        }
+        drop(out_log_date_locked);
+    }
+
+    /// Overwrite the progress line on the console with the update `msg`
+    fn output_progress_console(
+        &self,
+        config: &LogConfig,
+        now_str: &str,
+        log_level: LogLevel,
+        msg: &str,
+        is_process_stop: bool,
+    ) {
+        let to_stderr = config.console_to_stderr(log_level);
+        print_console(to_stderr, "\r", false);
+        self.output_console(
+            config,
+            now_str,
+            log_level,
+            None,
+            format_args!("{}", msg),
+            false,
+        );
+        // Blank what is left of a longer previous update behind the cursor
+        let width = config.format_prefix(now_str, log_level).chars().count() + msg.chars().count();
+        let mut last_width = self.last_progress_width.lock().unwrap();
+        print_console(
+            to_stderr,
+            &" ".repeat(last_width.saturating_sub(width)),
+            false,
+        );
+        *last_width = if is_process_stop { 0 } else { width };
+        drop(last_width);
+        if is_process_stop {
+            print_console(to_stderr, "", true);
+        }
+        flush_console(to_stderr);
+    }
+
+    /// Whether a progress update should be written now. Updates arriving within
+    /// `progress_min_update_interval` of the last written one are coalesced: only the latest
+    /// of them is kept, for `flush()`, and the next written update replaces it. The final
+    /// update is always written.
+    fn progress_render_due(
+        &self,
+        config: &LogConfig,
+        log_level: LogLevel,
+        msg: &str,
+        is_process_stop: bool,
+    ) -> bool {
+        let now = Instant::now();
+        let mut last_render = self.last_progress_render.lock().unwrap();
+        let mut pending = self.pending_progress.lock().unwrap();
+        if !is_process_stop {
+            if let Some(last_render) = *last_render {
+                if now - last_render < config.progress_min_update_interval {
+                    *pending = Some((log_level, msg.to_string()));
+                    return false;
                 }
-                LogLevel::Warn => output_warn_log!(now_str, "{}", msg),
-                _ => output_info_log!(now_str, "{}", msg),
             }
-            let _ = io::stdout().flush();
-            if let (Some(write_file), Some(write_date_file)) =
-                (self.out_log_file.as_ref(), self.out_log_date_file.as_ref())
-            {
-                let mut out_log_date_locked = self.out_log_date.lock().unwrap();
-                if now.date_naive() != *out_log_date_locked {
-                    // The dates are inconsistent; the logs need to be rolled over
-                    let log_dir = get_or_create_log_dir(self.class_name);
-                    let out_file_path = log_dir.join(format!("{}.log", self.class_name).as_str());
-                    let out_file = OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(&out_file_path)
-                        .unwrap_or_else(|_| {
-                            panic!(
-                                "Create log file: {} failed.",
-                                out_file_path.as_os_str().to_str().unwrap()
-                            )
-                        });
-                    let out_date_file_path =
-                        log_dir.join(format!("{}_{}.log", self.class_name, now_date_str).as_str());
-                    let out_date_file = OpenOptions::new()
-                        .append(true)
-                        .create(true)
-                        .open(&out_date_file_path)
-                        .unwrap_or_else(|_| {
-                            panic!(
-                                "Create log file: {} failed.",
-                                out_date_file_path.as_os_str().to_str().unwrap()
-                            )
-                        });
-                    let mut write_file = write_file.lock().unwrap();
-                    *write_file = out_file;
-                    let mut write_date_file = write_date_file.lock().unwrap();
-                    *write_date_file = out_date_file;
-                    *out_log_date_locked = now.date_naive();
+        }
+        *pending = None;
+        *last_render = if is_process_stop { None } else { Some(now) };
+        true
+    }
+
+    /// Write the latest progress update coalesced by `progress_min_update_interval`, so the
+    /// progress line doesn't stay behind while no further update arrives
+    fn flush_pending_progress(&self) {
+        let pending = self.pending_progress.lock().unwrap().take();
+        if let Some((log_level, msg)) = pending {
+            *self.last_progress_render.lock().unwrap() = Some(Instant::now());
+            self.write_progress(&self.config.load(), log_level, &msg, false);
+        }
+    }
+
+    /// Count records whose file writes failed. Once `MAX_FILE_WRITE_FAILURES` records in a row
+    /// failed (e.g. the volume was remounted read-only), file output is suspended with a single
+    /// message on stderr instead of failing again on every record.
+    fn track_file_write(&self, result: io::Result<()>) {
+        match result {
+            Ok(()) => self.file_write_failures.store(0, Ordering::Relaxed),
+            Err(e) => {
+                let failures = self.file_write_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                if failures >= MAX_FILE_WRITE_FAILURES
+                    && !self.files_suspended.swap(true, Ordering::Relaxed)
+                {
+                    eprintln!(
+                        "Writing the log files of {} failed {} times in a row ({}), \
+                         logging to the console only until recover_files() is called.",
+                        self.class_name, failures, e
+                    );
                 }
             }
-            // Write normally to the log of the current day
-            if let (Some(write_file), Some(line_position)) = (
-                self.out_log_file.as_ref(),
-                self.out_log_file_line_position.as_ref(),
-            ) {
-                let mut write_file = write_file.lock().unwrap();
-                let now_time = get_now_time_str!();
-                // Go back to the beginning of the line
-                let mut lp = line_position.lock().unwrap();
-                let _ = write_file.seek(io::SeekFrom::Start(*lp));
-
-                write!(write_file, "[{} {}] {}", now_time, log_level, msg).unwrap_or_else(|_f| {});
-                // Update lp
-                *lp = if let Ok(p) = write_file.stream_position() {
-                    if is_process_stop {
-                        p
-                    } else {
-                        p - (msg.len() + format!("[2024-05-08 12:24:05 {}] ", log_level).len())
-                            as u64
-                    }
-                } else {
-                    0
-                };
+        }
+    }
+
+    /// Build the `[+0.123s] ` annotation with the time elapsed since the previous record,
+    /// or an empty string when deltas are disabled
+    fn format_delta(&self, config: &LogConfig) -> String {
+        if !config.show_delta {
+            return String::new();
+        }
+        let now = Instant::now();
+        let last = self.last_record_time.lock().unwrap().replace(now);
+        let elapsed = last.map(|last| now - last).unwrap_or_default();
+        format!("[+{}] ", config.delta_format.format(elapsed))
+    }
+
+    /// Print a line to the console with the same coloring as the `output_*_log` macros
+    fn output_console(
+        &self,
+        config: &LogConfig,
+        time: impl fmt::Display,
+        log_level: LogLevel,
+        target: Option<&str>,
+        args: fmt::Arguments,
+        new_line: bool,
+    ) {
+        let line = render_console_line(config, time, log_level, target, args);
+        print_console(config.console_to_stderr(log_level), &line, new_line);
+    }
+}
+
+/// Print `text` to stdout, or to stderr for `LogConfig::warnings_to_stderr`
+fn print_console(to_stderr: bool, text: &str, new_line: bool) {
+    write_console(to_stderr, format_args!("{}", text), new_line);
+}
+
+/// Write `args` to stdout or stderr for the console output and the `output*` macros. Unlike
+/// `println!` a failed write, e.g. to a pipe whose reader exited (`EPIPE`), is ignored
+/// instead of panicking, the log files are still written.
+#[doc(hidden)]
+pub fn write_console(to_stderr: bool, args: fmt::Arguments, new_line: bool) {
+    if to_stderr {
+        write_ignoring_errors(&mut io::stderr().lock(), args, new_line);
+    } else {
+        write_ignoring_errors(&mut io::stdout().lock(), args, new_line);
+    }
+}
+
+/// Flush the stream `write_console` wrote to, so an unfinished line shows up
+fn flush_console(to_stderr: bool) {
+    let _ = if to_stderr {
+        io::stderr().flush()
+    } else {
+        io::stdout().flush()
+    };
+}
+
+fn write_ignoring_errors(out: &mut impl Write, args: fmt::Arguments, new_line: bool) {
+    let _ = out.write_fmt(args).and_then(|()| {
+        if new_line {
+            out.write_all(b"\n")
+        } else {
+            Ok(())
+        }
+    });
+}
+
+/// Format a console line. Without color it is plain text in the same format as the files.
+fn render_console_line(
+    config: &LogConfig,
+    time: impl fmt::Display,
+    log_level: LogLevel,
+    target: Option<&str>,
+    args: fmt::Arguments,
+) -> String {
+    #[cfg(feature = "color")]
+    let target = target
+        .and_then(|target| config.target_segment(target))
+        .map(|segment| {
+            if config.color {
+                segment.to_string()
+            } else {
+                segment.clear().to_string()
             }
-            if let (Some(write_file), Some(line_position)) = (
-                self.out_log_date_file.as_ref(),
-                self.out_log_date_file_line_position.as_ref(),
-            ) {
-                let mut write_file = write_file.lock().unwrap();
-                let now_time = get_now_time_str!();
-                // Go back to the beginning of the line
-                let mut lp = line_position.lock().unwrap();
-                let _ = write_file.seek(io::SeekFrom::Start(*lp));
-
-                write!(write_file, "[{} {}] {}", now_time, log_level, msg).unwrap_or_else(|_f| {});
-                // Update lp
-                *lp = if let Ok(p) = write_file.stream_position() {
-                    if is_process_stop {
-                        p
-                    } else {
-                        p - (msg.len() + calculate_log_prefix_len(&log_level))
-                            as u64
-                    }
-                } else {
-                    0
-                };
+        })
+        .unwrap_or_default();
+    #[cfg(not(feature = "color"))]
+    let target = target.map(|_| "").unwrap_or_default();
+    let msg = args.to_string();
+    let (label, msg) = if config.color {
+        (
+            style::styled_label(log_level, config.level_label),
+            style::message(log_level, &msg),
+        )
+    } else {
+        (
+            style::level_text(log_level, config.level_label).to_string(),
+            msg,
+        )
+    };
+    format!(
+        "{}{}{}",
+        config.format_prefix_with_label(time, label),
+        target,
+        msg
+    )
+}
+
+/// Remove the dated log files of `class_name` in `dir`, including rotated parts and
+/// compressed files, that are more than `max_days` days older than `today`. Files whose name
+/// doesn't carry a parseable date are left alone, and failures only print a warning.
+fn remove_expired_date_files(dir: &Path, class_name: &str, today: NaiveDate, max_days: u32) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Listing {} for log retention failed: {}", dir.display(), e);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some((date, _)) = name
+            .to_str()
+            .and_then(|name| split_date_file_name(class_name, name))
+        else {
+            continue;
+        };
+        if (today - date).num_days() > i64::from(max_days) {
+            if let Err(e) = fs::remove_file(entry.path()) {
+                eprintln!(
+                    "Removing expired log file {} failed: {}",
+                    entry.path().display(),
+                    e
+                );
             }
         }
     }
 }
 
-#[inline]
-fn calculate_log_prefix_len(log_level: &LogLevel) -> usize {
-    format!("[2024-05-08 12:24:05 {}] ", log_level).len()
+/// The date of a dated file name of `class_name` and what follows it, e.g. `.log` for
+/// `<class_name>_20240508.log`, `.1.log` for a rotated part or `.log.gz` when compressed.
+/// `None` for other files, including the latest file, level files and invalid dates.
+fn split_date_file_name<'a>(class_name: &str, name: &'a str) -> Option<(NaiveDate, &'a str)> {
+    let rest = name.strip_prefix(class_name)?.strip_prefix('_')?;
+    let (date, suffix) = (rest.get(..8)?, rest.get(8..)?);
+    if !suffix.starts_with('.') || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(date, "%Y%m%d").ok()?;
+    Some((date, suffix))
+}
+
+/// Name of the dated log file of `date`, with the part number after size rotations
+fn date_file_name(class_name: &str, date: NaiveDate, index: u32) -> String {
+    match index {
+        0 => format!("{}_{}.log", class_name, date.format("%Y%m%d")),
+        index => format!("{}_{}.{}.log", class_name, date.format("%Y%m%d"), index),
+    }
+}
+
+/// Write `line` and a line break with a single `write_all` of the formatted buffer.
+/// `write_all` retries short writes, so the line is either written completely or the error is
+/// returned, e.g. `WriteZero` when the disk is full.
+pub(crate) fn write_full_line(sink: &mut impl Write, line: &str) -> io::Result<()> {
+    let mut buffer = Vec::with_capacity(line.len() + 1);
+    buffer.extend_from_slice(line.as_bytes());
+    buffer.push(b'\n');
+    sink.write_all(&buffer)
+}
+
+/// Overwrite the progress line that starts at `line_position` with `line`, and remember where
+/// the next update has to start. Sinks that can't seek (e.g. pipes) can't overwrite, so there
+/// every update is appended as a line of its own instead.
+fn write_progress_line(
+    write_file: &mut LogSink,
+    line_position: &mut u64,
+    line: &str,
+    is_process_stop: bool,
+) {
+    // Go back to the beginning of the line
+    if write_file
+        .seek(io::SeekFrom::Start(*line_position))
+        .is_err()
+    {
+        let _ = write_full_line(write_file, line).and_then(|()| write_file.flush());
+        return;
+    }
+    // The final update ends the line, the next record starts after it
+    let _ = if is_process_stop {
+        write_full_line(write_file, line)
+    } else {
+        write_file.write_all(line.as_bytes())
+    };
+    // Update lp
+    *line_position = if let Ok(p) = write_file.stream_position() {
+        // Cut off the rest of a longer previous update, the progress line is the last one
+        let _ = write_file.set_len(p);
+        if is_process_stop {
+            p
+        } else {
+            p - line.len() as u64
+        }
+    } else {
+        0
+    };
+}
+
+/// Build the `[task 12] ` annotation with the id of the current tokio task, or an empty
+/// string when task ids are disabled or the record is not logged from inside a task
+#[cfg(feature = "tokio")]
+fn format_task_id(config: &LogConfig) -> String {
+    match tokio::task::try_id() {
+        Some(id) if config.show_task_id => format!("[task {}] ", id),
+        _ => String::new(),
+    }
+}
+
+/// Whether the console shows where errors were logged unless `set_show_location` says
+/// otherwise: in debug builds only
+pub(crate) const DEFAULT_SHOW_LOCATION: bool = !IS_RELEASE_BUILD;
+
+/// `[file:line] ` of an error on the console with `LogConfig::show_location`, empty otherwise
+fn console_location(config: &LogConfig, event: &LogEvent) -> String {
+    match (&event.file, event.line) {
+        (Some(file), Some(line)) if config.show_location => format!("[{file}:{line}] "),
+        _ => String::new(),
+    }
+}
+
+impl LogUtil {
+    /// Count an event that passed the level filter and write it, or queue it on an async logger
+    pub(crate) fn dispatch(&self, event: LogEvent) {
+        self.counts.increment(LogLevel::from(event.level));
+        let config = self.config.load_full();
+        // The sinks without a level of their own filter by this one
+        let level = config
+            .target_level(&event.target)
+            .unwrap_or_else(|| self.level());
+        match &self.background {
+            Some(background) => background.send(event, config, level),
+            None => self.write_event(&config, event, level),
+        }
+    }
+
+    /// The time of a new record from the logger's clock
+    #[cfg(feature = "tracing")]
+    pub(crate) fn now(&self) -> DateTime<chrono::Local> {
+        self.clock.now()
+    }
+
+    /// Write a record that passed the level filter to the console and all sinks with `config`
+    /// whose level accepts it, `level` being the logger's level for the record. Async loggers
+    /// call it on their writer thread.
+    pub(crate) fn write_event(&self, config: &LogConfig, mut event: LogEvent, level: LevelFilter) {
+        let accepts = |sink: Sink| event.level <= config.sink_level(sink, level);
+        let (to_console, to_files) = (accepts(Sink::Console), accepts(Sink::Files));
+        #[cfg(all(unix, feature = "syslog"))]
+        let to_syslog = accepts(Sink::Syslog);
+        let to_captures = accepts(Sink::Capture);
+        if let Cow::Owned(redacted) = self.redact(config, &event.message) {
+            event.message = redacted;
+        }
+        for (_, value) in event.kv.iter_mut() {
+            if let Cow::Owned(redacted) = self.redact(config, value) {
+                *value = redacted;
+            }
+        }
+        let message = event.message.clone() + &event.format_fields();
+        // The date of this zone decides the rollover and the dated file name too
+        let now = config.time_zone.convert(&event.time);
+        let now_str = config.format_time(&now);
+        let annotations = if config.show_thread {
+            format!("[{}] ", event.thread)
+        } else {
+            String::new()
+        } + &self.format_delta(config);
+        let annotations = match &event.span {
+            Some(span) => annotations + span + ": ",
+            None => annotations,
+        };
+        #[cfg(feature = "tokio")]
+        let annotations = annotations + &format_task_id(config);
+        // The line of a custom formatter replaces the standard line everywhere
+        let formatted = self
+            .formatter
+            .load()
+            .as_ref()
+            .map(|formatter| formatter(&event));
+        match event.level {
+            // Records of silenced targets only go to the files
+            _ if !to_console || config.is_console_silenced(&event.target) => {}
+            _ if formatted.is_some() => {
+                let level = LogLevel::from(event.level);
+                let line = formatted.as_deref().unwrap_or_default();
+                let line = if config.color {
+                    style::message(level, line)
+                } else {
+                    line.to_string()
+                };
+                print_console(config.console_to_stderr(level), &line, true)
+            }
+            #[cfg(feature = "serde_json")]
+            _ if config.json_console && config.format == Format::Json => print_console(
+                config.console_to_stderr(LogLevel::from(event.level)),
+                &event.render_json(&now_str),
+                true,
+            ),
+            Level::Error => {
+                let log_location_str = console_location(config, &event);
+                self.output_console(
+                    config,
+                    &now_str,
+                    LogLevel::Error,
+                    Some(&event.target),
+                    format_args!("{}{}{}", annotations, log_location_str, message),
+                    true,
+                )
+            }
+            level => self.output_console(
+                config,
+                &now_str,
+                LogLevel::from(level),
+                Some(&event.target),
+                format_args!("{}{}", annotations, message),
+                true,
+            ),
+        }
+        let body = format!("{}{}", annotations, message);
+        if to_captures {
+            if let Some(captured) = &self.captured {
+                let record = (LogLevel::from(event.level), message.clone());
+                let mut captured = captured.lock().unwrap();
+                // Bounded, so a flood of unique messages can't grow the memory without end
+                while !captured.is_empty() && captured.len() >= config.max_tracked_messages {
+                    captured.pop_front();
+                }
+                if config.max_tracked_messages > 0 {
+                    captured.push_back(record);
+                }
+            }
+            match &formatted {
+                Some(line) => self.captures.write_line(line),
+                None => self.captures.write_line(&format!(
+                    "{}{}",
+                    config.format_prefix(&now_str, event.level),
+                    body
+                )),
+            }
+        }
+        #[cfg(all(unix, feature = "syslog"))]
+        if let (Some(syslog), true) = (&self.syslog, to_syslog) {
+            let _ = syslog.send(
+                event.level,
+                &event.time,
+                formatted.as_deref().unwrap_or(&body),
+            );
+        }
+        if self.recent.is_enabled() {
+            let line = match &formatted {
+                Some(line) => line.clone(),
+                None => config
+                    .format_file_line(&event, &now_str, &body)
+                    .trim_end_matches('\n')
+                    .to_string(),
+            };
+            self.recent.push(line);
+        }
+        let body = if config.show_source {
+            event.source_segment() + &body
+        } else {
+            body
+        };
+        if to_files {
+            let mut zone_trees = self.zone_trees.lock().unwrap();
+            for i in 0..zone_trees.len() {
+                let _ =
+                    zone_trees[i].write_record(now.with_timezone(&Utc), config, event.level, &body);
+            }
+            if let Some(max_open) = config.max_open_zone_files {
+                zone_tree::close_least_recently_used(&mut zone_trees, max_open);
+            }
+        }
+        // After repeated write failures only the console is used until recover_files()
+        if to_files && !self.files_suspended.load(Ordering::Relaxed) {
+            // The writes happen under the date lock too, so a record goes to both files of the
+            // same day
+            let (out_log_date_locked, rolled) = self.ensure_current_day(config, &now);
+            let line = match &formatted {
+                Some(line) => format!("{line}\n"),
+                None => config.format_file_line(&event, &now_str, &body),
+            };
+            // A record that can't go to the files of its day counts as a failed write
+            let write_result = rolled
+                .and_then(|()| self.dedup_record(config, &event, &message, &now_str))
+                .and_then(|repeated| {
+                    if repeated {
+                        return Ok(());
+                    }
+                    self.rotate_date_file_if_full(config, *out_log_date_locked)
+                        .and_then(|()| self.write_line_to_files(config, line.as_bytes()))
+                        .and_then(|()| {
+                            self.write_level_file(config, event.level, line.as_bytes())
+                        })?;
+                    #[cfg(feature = "serde_json")]
+                    self.write_json_file(config, &event, &now_str)?;
+                    Ok(())
+                });
+            drop(out_log_date_locked);
+            let write_result =
+                write_result.and_then(|()| self.flush_files_if_due(config, event.level));
+            self.track_file_write(write_result);
+        }
+        for callback in self.record_callbacks.load().iter() {
+            callback(&event);
+        }
+    }
 }
 
-include!(concat!(env!("OUT_DIR"), "/version_info.rs"));
 impl log::Log for LogUtil {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let config = self.config.load();
+        if let Some(filter) = &config.target_filter {
+            if !metadata.target().contains(filter.as_str()) {
+                return false;
+            }
+        }
+        let level = config
+            .target_level(metadata.target())
+            .unwrap_or_else(|| self.level());
+        metadata.level() <= config.max_sink_level(level)
     }
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            let now = chrono::Local::now();
-            let now_str = now.format("%Y-%m-%d %H:%M:%S");
-            let now_date_str = now.format("%Y%m%d");
-            match record.level() {
-                Level::Debug => output_debug_log_ln!(now_str, "{}", record.args()),
-                Level::Error => {
-                    let log_location_str = if !IS_RELEASE {
-                        if let (Some(file), Some(line)) = (record.file(), record.line()) {
-                            format!("[{file}:{line}] ")
-                        } else {
-                            String::new()
-                        }
-                    } else {
-                        String::new()
-                    };
-                    output_error_log_ln!(now_str, "{}{}", log_location_str, record.args())
+            // The event is built on the logging thread, so it has its time and thread name
+            self.dispatch(LogEvent::from_record_at(record, self.clock.now()));
+        }
+    }
+    fn flush(&self) {
+        if let Some(background) = &self.background {
+            background.drain();
+        }
+        self.flush_pending_progress();
+        self.flush_repeat_summary();
+        let _ = self.flush_files();
+    }
+}
+
+/// Returned by `LogUtil::bootstrap`, stops the heartbeat and the rollover timer and flushes the
+/// console and the log files when dropped
+#[must_use = "the logger is flushed when the guard is dropped"]
+pub struct LogGuard {
+    logger: &'static LogUtil,
+}
+
+impl LogGuard {
+    /// The installed global logger
+    pub fn logger(&self) -> &'static LogUtil {
+        self.logger
+    }
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        self.logger.disable_heartbeat();
+        self.logger.stop_rollover_timer();
+        let _ = io::stdout().flush();
+        self.logger.flush();
+    }
+}
+
+/// Flush the log files when an owned logger goes away, e.g. one used for
+/// `output_progress_msg` in a short-lived program. A logger installed with `init` or
+/// `init_with_logger` is `'static` and is normally never dropped, use `Log::flush` or the
+/// `LogGuard` of `bootstrap` for it.
+impl Drop for LogUtil {
+    fn drop(&mut self) {
+        self.flush();
+        if let Some(background) = self.background.take() {
+            background.stop();
+        }
+    }
+}
+
+fn fetch_max_level_from_env() -> LevelFilter {
+    parse_level_directives(&std::env::var("RUST_LOG").unwrap_or_default()).0
+}
+
+/// Parse a level name in any case, or a number from `0` (off) to `5` (trace)
+fn parse_level(level: &str) -> Option<LevelFilter> {
+    match level.trim().to_ascii_lowercase().as_str() {
+        "0" => Some(LevelFilter::Off),
+        "1" => Some(LevelFilter::Error),
+        "2" => Some(LevelFilter::Warn),
+        "3" => Some(LevelFilter::Info),
+        "4" => Some(LevelFilter::Debug),
+        "5" => Some(LevelFilter::Trace),
+        "info" => Some(LevelFilter::Info),
+        "debug" => Some(LevelFilter::Debug),
+        "error" => Some(LevelFilter::Error),
+        "warn" => Some(LevelFilter::Warn),
+        "off" => Some(LevelFilter::Off),
+        "trace" => Some(LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+/// Split an env_logger style spec like `mycrate::db=debug,warn` into the global level
+/// (`Info` when missing) and the `(target_prefix, level)` rules. A bare module name enables
+/// all of its levels, unparseable directives are ignored.
+fn parse_level_directives(spec: &str) -> (LevelFilter, Vec<(String, LevelFilter)>) {
+    let mut global = LevelFilter::Info;
+    let mut rules = Vec::new();
+    for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((target, level)) => {
+                if let Some(level) = parse_level(level) {
+                    rules.push((target.to_string(), level));
                 }
-                Level::Warn => output_warn_log_ln!(now_str, "{}", record.args()),
-                _ => output_info_log_ln!(now_str, "{}", record.args()),
             }
-            if let (Some(write_file), Some(write_date_file)) =
-                (self.out_log_file.as_ref(), self.out_log_date_file.as_ref())
-            {
-                let mut out_log_date_locked = self.out_log_date.lock().unwrap();
-                if now.date_naive() != *out_log_date_locked {
-                    // The dates are inconsistent; the logs need to be rolled over.
-                    let log_dir = get_or_create_log_dir(self.class_name);
-                    let out_file_path = log_dir.join(format!("{}.log", self.class_name).as_str());
-                    let out_file = OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .truncate(true)
-                        .open(&out_file_path)
-                        .unwrap_or_else(|_| {
-                            panic!(
-                                "Create log file: {} failed.",
-                                out_file_path.as_os_str().to_str().unwrap()
-                            )
-                        });
-                    let out_date_file_path =
-                        log_dir.join(format!("{}_{}.log", self.class_name, now_date_str).as_str());
-                    let mut out_date_file = OpenOptions::new()
-                        // .append(true)
-                        .write(true)
-                        .read(true)
-                        .create(true)
-                        .open(&out_date_file_path)
-                        .unwrap_or_else(|_| {
-                            panic!(
-                                "Create log file: {} failed.",
-                                out_date_file_path.as_os_str().to_str().unwrap()
-                            )
-                        });
-                    // Jump to the end of the file before starting to write
-                    let _ = out_date_file.seek(io::SeekFrom::End(0));
-                    let mut write_file = write_file.lock().unwrap();
-                    *write_file = out_file;
-                    let mut write_date_file = write_date_file.lock().unwrap();
-                    *write_date_file = out_date_file;
-                    *out_log_date_locked = now.date_naive();
-                }
+            None => match parse_level(directive) {
+                Some(level) => global = level,
+                None => rules.push((directive.to_string(), LevelFilter::Trace)),
+            },
+        }
+    }
+    (global, rules)
+}
+
+/// The max level the `log` facade has to let through so that `global` and every rule can
+/// still match in `enabled`
+fn facade_max_level(global: LevelFilter, rules: &[(String, LevelFilter)]) -> LevelFilter {
+    rules.iter().map(|(_, level)| *level).fold(global, Ord::max)
+}
+
+/// The log root of `LogUtil::new`: `$LOG_UTIL_DIR` when set, `log` otherwise
+pub(crate) fn default_log_root() -> PathBuf {
+    std::env::var_os("LOG_UTIL_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("log"))
+}
+
+/// `path` with every `$NAME` and `${NAME}` replaced by the value of the environment variable
+/// `NAME`. A variable that is not set is an error, so logs don't end up in a surprising place.
+pub(crate) fn expand_env_vars(path: &Path) -> io::Result<PathBuf> {
+    let path = path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Log dir {} is not valid UTF-8", path.display()),
+        )
+    })?;
+    let mut expanded = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, next) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => (braced, ""),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
             }
-            // Write normally to the log of the current day
-            if let (Some(write_file), Some(line_position)) = (
-                self.out_log_file.as_ref(),
-                self.out_log_file_line_position.as_ref(),
-            ) {
-                let mut write_file = write_file.lock().unwrap();
-                let now_time = get_now_time_str!();
-                writeln!(
-                    write_file,
-                    "[{} {}] {}",
-                    now_time,
-                    record.level(),
-                    record.args()
-                )
-                .unwrap_or_else(|_f| {});
-                // modify the position at the beginning of the line
-                let mut lp = line_position.lock().unwrap();
-                *lp = write_file.stream_position().unwrap_or_default();
-            }
-            if let (Some(write_file), Some(line_position)) = (
-                self.out_log_date_file.as_ref(),
-                self.out_log_date_file_line_position.as_ref(),
-            ) {
-                let mut write_file = write_file.lock().unwrap();
-                let now_time = get_now_time_str!();
-                writeln!(
-                    write_file,
-                    "[{} {}] {}",
-                    now_time,
-                    record.level(),
-                    record.args()
+        };
+        if name.is_empty() {
+            // A lone `$` is kept
+            expanded.push('$');
+        } else {
+            let value = std::env::var(name).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Log dir {path}: environment variable {name} is not set"),
                 )
-                .unwrap_or_else(|_f| {});
-                // modify the position at the beginning of the line
-                let mut lp = line_position.lock().unwrap();
-                *lp = write_file.stream_position().unwrap_or_default();
-            }
+            })?;
+            expanded.push_str(&value);
         }
+        rest = next;
     }
-    fn flush(&self) {}
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
 }
 
-fn fetch_max_level_from_env() -> LevelFilter {
-    match std::env::var("RUST_LOG").unwrap_or_default().as_str() {
-        "info" => LevelFilter::Info,
-        "debug" => LevelFilter::Debug,
-        "error" => LevelFilter::Error,
-        "warn" => LevelFilter::Warn,
-        "off" => LevelFilter::Off,
-        "trace" => LevelFilter::Trace,
-        _ => LevelFilter::Info,
+/// A class name is a file name, so it can't lead the log files out of their directory
+fn check_class_name(class_name: &str) -> io::Result<()> {
+    if class_name == "." || class_name == ".." || class_name.contains(['/', '\\', '\0']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid class name {class_name:?}: it must be usable as a file name"),
+        ));
     }
+    Ok(())
 }
 
-fn get_or_create_log_dir(class_name: &str) -> PathBuf {
-    let log_dir = Path::new("log");
-    if !log_dir.exists() {
-        fs::create_dir(log_dir).expect("Create log dir failed.");
-    }
-    let log_dir = log_dir.join(class_name);
+/// Default permission bits of created log directories (Unix only)
+pub const DEFAULT_DIR_MODE: u32 = 0o755;
+
+/// Open the latest file `<class_name>.log` for writing at its end, emptied unless `mode` is
+/// `MainLogMode::Append`
+fn open_main_log(path: &Path, mode: MainLogMode) -> io::Result<fs::File> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(mode != MainLogMode::Append)
+        .open(path)?;
+    file.seek(io::SeekFrom::End(0))?;
+    Ok(file)
+}
+
+/// Keep the kind of `error` and add the log file `path` to its message
+fn log_file_error(path: &Path, error: io::Error) -> io::Error {
+    io::Error::new(
+        error.kind(),
+        format!("Create log file: {} failed: {}", path.display(), error),
+    )
+}
+
+fn try_create_log_dir(log_dir: &Path, dir_mode: u32) -> io::Result<()> {
     if !log_dir.exists() {
-        fs::create_dir(log_dir.clone())
-            .unwrap_or_else(|_| panic!("Create log/{class_name} dir failed."));
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(dir_mode);
+        }
+        #[cfg(not(unix))]
+        let _ = dir_mode;
+        builder.create(log_dir).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Create {} dir failed: {}", log_dir.display(), e),
+            )
+        })?;
     }
-    log_dir
+    Ok(())
 }
 
 impl LogUtil {
@@ -394,79 +1214,3638 @@ impl LogUtil {
     }
 
     pub fn init_with_logger(logger: &'static LogUtil) -> Result<&'static LogUtil, SetLoggerError> {
-        let max_level = fetch_max_level_from_env();
+        let config = logger.config();
+        let max_level = facade_max_level(
+            config.max_sink_level(Self::max_level()),
+            &config.target_levels,
+        );
         log::set_logger(logger).map(|()| log::set_max_level(max_level))?;
+        #[cfg(feature = "color")]
+        if !io::stdout().is_terminal() {
+            // Keep escape codes of the `output_*_log` macros out of pipes and files. Only done
+            // once installed, so a failed call leaves the colors of the running logger alone.
+            colored::control::set_override(false);
+        }
         Ok(logger)
     }
+
+    /// Like `init`, but a global logger that is already installed, by an earlier call or by
+    /// another logging crate, is not an error: returns `Ok(true)` when the logger was installed
+    /// now and `Ok(false)` when one was present already. Useful in tests that each initialize.
+    pub fn try_init() -> Result<bool, SetLoggerError> {
+        Self::try_init_with_logger(&LOGGER)
+    }
+
+    /// `try_init` with the given logger instead of the default one
+    pub fn try_init_with_logger(logger: &'static LogUtil) -> Result<bool, SetLoggerError> {
+        // `set_logger` only fails when a logger is set
+        Ok(Self::init_with_logger(logger).is_ok())
+    }
+
+    /// Log panics as errors through the installed logger and flush it before the previous
+    /// panic hook runs, so the panic message also ends up in the log files. The record holds
+    /// the message and the location, followed by the backtrace when `RUST_BACKTRACE` (or
+    /// `RUST_LIB_BACKTRACE`) enables it.
+    pub fn install_panic_hook() {
+        Self::set_panic_hook(None);
+    }
+
+    /// `install_panic_hook` which also prints the `recent` lines of `logger` to stderr after
+    /// the panic record, e.g. to copy them into an error report when the files are buffered or
+    /// the disk is full
+    pub fn install_panic_hook_with_recent(logger: &'static LogUtil) {
+        Self::set_panic_hook(Some(logger));
+    }
+
+    fn set_panic_hook(dump_recent: Option<&'static LogUtil>) {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let backtrace = Backtrace::capture();
+            match backtrace.status() {
+                BacktraceStatus::Captured => {
+                    log::error!("{}\nstack backtrace:\n{}", info, backtrace)
+                }
+                _ => log::error!("{}", info),
+            }
+            log::logger().flush();
+            if let Some(logger) = dump_recent {
+                let recent = logger.recent();
+                write_console(true, format_args!("recent log lines:"), true);
+                for line in &recent {
+                    write_console(true, format_args!("{line}"), true);
+                }
+            }
+            previous(info);
+        }));
+    }
+
+    /// The usual setup of a binary in one call: create a logger for `class_name`, install it
+    /// as the global logger with the max level from `RUST_LOG` and install the panic hook.
+    /// Keep the returned guard alive in `main`, the logger is flushed when it is dropped.
+    /// When a global logger is installed already it fails with `ErrorKind::AlreadyExists` and
+    /// the new logger is dropped again.
+    pub fn bootstrap(class_name: &'static str) -> io::Result<LogGuard> {
+        let logger = Box::into_raw(Box::new(LogUtil::try_new(class_name)?));
+        // SAFETY: `logger` comes from `Box::into_raw`, it is only freed when it was not installed
+        let installed: &'static LogUtil = unsafe { &*logger };
+        if let Err(e) = Self::init_with_logger(installed) {
+            // SAFETY: `set_logger` failed, so no reference to the logger was kept
+            drop(unsafe { Box::from_raw(logger) });
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, e.to_string()));
+        }
+        Self::install_panic_hook();
+        Ok(LogGuard { logger: installed })
+    }
+
+    /// Create a logger writing to `<root>/<class_name>/`. The root is `log` in the current
+    /// directory unless the `LOG_UTIL_DIR` environment variable names another one. When the
+    /// files can't be created the logger only prints to the console, see `try_new`.
+    /// The latest file `<class_name>.log` starts empty on every start and every day while the
+    /// dated `<class_name>_YYYYMMDD.log` appends, see `LogUtilBuilder::main_log_mode` to keep it.
     pub fn new(class_name: &'static str) -> LogUtil {
-        let now_date = chrono::Local::now().date_naive();
-        let (out_file, out_date_file) = if class_name.is_empty() {
+        Self::new_with_dir_mode(class_name, DEFAULT_DIR_MODE)
+    }
+
+    /// Create a logger that only prints to the console and never creates a log directory or
+    /// file. This is the logger of `init`, and the same as `LogUtil::new("")`.
+    pub fn console_only() -> LogUtil {
+        // Without a class name no files are opened, so building can't fail
+        Self::builder().with_files(false).build().unwrap()
+    }
+
+    /// Same as `new`, but with the log files under `root` instead of the default root,
+    /// e.g. `/var/log/myapp`
+    pub fn new_in(class_name: &'static str, root: impl AsRef<Path>) -> LogUtil {
+        Self::build_or_console_only(
+            Self::builder()
+                .class_name(class_name)
+                .root_dir(root.as_ref().to_path_buf()),
+        )
+    }
+
+    /// Same as `new`, but log directories are created with the given permission bits on Unix.
+    /// The mode is ignored on other platforms.
+    pub fn new_with_dir_mode(class_name: &'static str, dir_mode: u32) -> LogUtil {
+        Self::build_or_console_only(Self::builder().class_name(class_name).dir_mode(dir_mode))
+    }
+
+    /// The constructors without a `Result` don't stop a program whose log directory can't be
+    /// created, e.g. on a read-only file system: they warn once on stderr and only log to the
+    /// console
+    fn build_or_console_only(builder: LogUtilBuilder) -> LogUtil {
+        let console_only = builder.clone().with_files(false);
+        builder.build().unwrap_or_else(|e| {
+            eprintln!("log_util: {e}, logging to the console only");
+            // Without files building can't fail
+            console_only.build().unwrap()
+        })
+    }
+
+    /// Same as `new`, but a log directory or file that can't be created is returned as an
+    /// error instead of falling back to the console. The error message names the offending
+    /// path.
+    pub fn try_new(class_name: &'static str) -> io::Result<LogUtil> {
+        Self::builder().class_name(class_name).build()
+    }
+
+    /// Start configuring a logger with all options in one place, e.g.
+    /// `LogUtil::builder().class_name("Server").color(false).build()`
+    pub fn builder() -> LogUtilBuilder {
+        LogUtilBuilder::default()
+    }
+
+    pub(crate) fn try_create(
+        class_name: &'static str,
+        log_dir: PathBuf,
+        dir_mode: u32,
+        with_files: bool,
+        config: LogConfig,
+        clock: Arc<dyn Clock>,
+    ) -> io::Result<LogUtil> {
+        let now_date = config.time_zone.convert(&clock.now()).date_naive();
+        let (out_file, out_date_file) = if class_name.is_empty() || !with_files {
             (None, None)
         } else {
-            let log_dir = get_or_create_log_dir(class_name);
+            check_class_name(class_name)?;
+            try_create_log_dir(&log_dir, dir_mode)?;
             let now_date_str = now_date.format("%Y%m%d").to_string();
             let out_file_path = log_dir.join(format!("{class_name}.log").as_str());
-            let out_file = Arc::new(Mutex::new(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&out_file_path)
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Create log file: {} failed.",
-                            out_file_path.as_os_str().to_str().unwrap()
-                        )
-                    }),
-            ));
+            let out_file = if config.main_log {
+                let file = open_main_log(&out_file_path, config.main_log_mode)
+                    .map_err(|e| log_file_error(&out_file_path, e))?;
+                Some(Arc::new(Mutex::new(file.into())))
+            } else {
+                None
+            };
             let out_date_file_path =
                 log_dir.join(format!("{class_name}_{now_date_str}.log").as_str());
-            let out_date_file = Arc::new(Mutex::new({
-                let mut f = OpenOptions::new()
-                    // .append(true)
-                    .write(true)
-                    .read(true)
-                    .create(true)
-                    .open(&out_date_file_path)
-                    .unwrap_or_else(|_| {
-                        panic!(
-                            "Create log file: {} failed.",
-                            out_date_file_path.as_os_str().to_str().unwrap()
-                        )
-                    });
-                let _ = f.seek(io::SeekFrom::End(0));
-                f
-            }));
+            let mut out_date_file = OpenOptions::new()
+                // .append(true)
+                .write(true)
+                .read(true)
+                .create(true)
+                .truncate(false)
+                .open(&out_date_file_path)
+                .map_err(|e| log_file_error(&out_date_file_path, e))?;
             // Jump to the end of the file before beginning to write
-            (
-                Some(Arc::clone(&out_file)),
-                Some(Arc::clone(&out_date_file)),
-            )
+            let _ = out_date_file.seek(io::SeekFrom::End(0));
+            (out_file, Some(Arc::new(Mutex::new(out_date_file.into()))))
         };
-        LogUtil {
+        Ok(LogUtil {
             class_name,
+            // A line position only exists for a file that is actually written
+            out_log_file_line_position: out_file.as_ref().map(|_| Arc::new(Mutex::new(0))),
             out_log_file: out_file,
-            out_log_file_line_position: Some(Arc::new(Mutex::new(0))),
+            out_log_date_file_line_position: out_date_file
+                .as_ref()
+                .map(|_| Arc::new(Mutex::new(0))),
             out_log_date_file: out_date_file,
-            out_log_date_file_line_position: Some(Arc::new(Mutex::new(0))),
-            init_date: now_date,
             out_log_date: Arc::new(Mutex::new(now_date)),
+            out_log_date_file_index: Arc::new(AtomicU32::new(0)),
+            level_files: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "serde_json")]
+            json_file: Arc::new(Mutex::new(None)),
+            last_record: Arc::new(Mutex::new(None)),
+            level: AtomicUsize::new(LEVEL_INHERIT),
+            parent: None,
+            config: ArcSwap::from_pointee(config),
+            log_dir,
+            single_file_path: None,
+            dir_mode,
+            last_record_time: Mutex::new(None),
+            last_progress_render: Mutex::new(None),
+            pending_progress: Mutex::new(None),
+            last_progress_width: Mutex::new(0),
+            captures: Arc::new(CaptureSinks::default()),
+            captured: None,
+            zone_trees: Arc::new(Mutex::new(Vec::new())),
+            record_callbacks: Arc::new(ArcSwap::from_pointee(Vec::new())),
+            formatter: Arc::new(ArcSwapOption::empty()),
+            redactor: Arc::new(ArcSwapOption::empty()),
+            counts: Arc::new(LevelCounters::default()),
+            recent: Arc::new(RecentRecords::default()),
+            files_suspended: AtomicBool::new(false),
+            file_write_failures: AtomicUsize::new(0),
+            heartbeat: Mutex::new(None),
+            rollover_timer: Mutex::new(None),
+            #[cfg(all(unix, feature = "syslog"))]
+            syslog: None,
+            background: None,
+            last_flush: Mutex::new(Instant::now()),
+            clock,
+        })
+    }
+
+    /// Create a logger that appends to the single file at `path` instead of the latest and
+    /// dated files under `log/<class_name>/`. Every line keeps its full timestamp and the file
+    /// is never rolled over, so it can be rotated externally, e.g. with logrotate. Since the
+    /// file is opened for appending, progress updates are appended instead of overwritten.
+    /// There is no dated file, so `tail_today` and `read_today` return nothing.
+    pub fn single_file(class_name: &'static str, path: impl AsRef<Path>) -> io::Result<LogUtil> {
+        let path = path.as_ref();
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        let mut logger = LogUtil::console_only();
+        logger.class_name = class_name;
+        // Appends can't be positioned, so the file is written like a custom writer
+        let sink = LogSink::Writer(Box::new(BufWriter::new(file)));
+        logger.out_log_file = Some(Arc::new(Mutex::new(sink)));
+        logger.out_log_file_line_position = Some(Arc::new(Mutex::new(0)));
+        logger.single_file_path = Some(path.to_path_buf());
+        Ok(logger)
+    }
+
+    /// Create a logger that writes its file output to `writer` instead of files under
+    /// `log/<class_name>/`, e.g. an in-memory buffer in tests or a network socket. The lines
+    /// are the same as in the latest log file. Nothing is rolled over, and since the writer
+    /// can't seek, progress updates are appended as lines of their own.
+    pub fn with_writer(class_name: &'static str, writer: impl Write + Send + 'static) -> LogUtil {
+        let mut logger = LogUtil::console_only();
+        logger.class_name = class_name;
+        logger.out_log_file = Some(Arc::new(Mutex::new(LogSink::Writer(Box::new(writer)))));
+        logger.out_log_file_line_position = Some(Arc::new(Mutex::new(0)));
+        logger
+    }
+
+    /// Create a logger that sends its file lines to the collector at `addr` over TCP, one line
+    /// per record, instead of writing log files. See `with_tcp_options` for the queueing.
+    pub fn with_tcp(class_name: &'static str, addr: SocketAddr) -> LogUtil {
+        Self::with_tcp_options(class_name, addr, TcpOptions::default())
+    }
+
+    /// Like `with_tcp`. The lines are queued and sent by a background thread, so logging
+    /// doesn't wait for the network. The thread connects on demand, and after a failed
+    /// connection or write it reconnects every `reconnect_delay` and sends the interrupted line
+    /// again. While the collector is unreachable the lines wait in the queue, and once
+    /// `queue_capacity` lines are waiting `when_full` decides: `WhenFull::Drop` (the default)
+    /// discards new lines, `WhenFull::Block` makes logging wait for room.
+    pub fn with_tcp_options(
+        class_name: &'static str,
+        addr: SocketAddr,
+        options: TcpOptions,
+    ) -> LogUtil {
+        Self::with_writer(class_name, TcpSender::spawn(addr, options))
+    }
+
+    /// Create a logger that sends its records to the local syslog daemon instead of writing log
+    /// files, e.g. for a service whose output journald or rsyslog already collects. Each
+    /// record is one RFC 5424 message with the syslog severity of its level and the facility
+    /// and app name of `options`. The console is still written. Fails when the socket can't be
+    /// connected, a message that can't be sent later is dropped.
+    #[cfg(all(unix, feature = "syslog"))]
+    pub fn with_syslog(class_name: &'static str, options: SyslogOptions) -> io::Result<LogUtil> {
+        let mut logger = LogUtil::console_only();
+        logger.class_name = class_name;
+        logger.syslog = Some(Arc::new(SyslogSink::connect(options, class_name)?));
+        Ok(logger)
+    }
+
+    /// Create a logger that writes the files on a dedicated thread, so `Log::log` only queues
+    /// the record and returns. The queue holds up to 4096 records, when it is full logging
+    /// waits for the writer. `Log::flush` waits until the queued records are written, and
+    /// dropping the logger writes the rest and stops the thread. Progress updates and child
+    /// loggers still write synchronously.
+    pub fn new_async(class_name: &'static str) -> LogUtil {
+        let writer = Arc::new(LogUtil::new(class_name));
+        let mut logger = writer.share(None);
+        logger.background = Some(BackgroundWriter::spawn(writer));
+        logger
+    }
+
+    /// Create a logger that keeps the level and message of every record in memory instead of
+    /// writing log files, e.g. to assert in tests that a function logged a warning. Install
+    /// it like any logger and read the records with `captured`. The console is still written.
+    /// Only the last `set_max_tracked_messages` records are kept.
+    pub fn capturing(class_name: &'static str) -> LogUtil {
+        let mut logger = LogUtil::console_only();
+        logger.class_name = class_name;
+        logger.captured = Some(Arc::new(Mutex::new(VecDeque::new())));
+        logger
+    }
+
+    /// The records of a `capturing` logger so far, oldest first. Messages are redacted like in
+    /// the files. Other loggers return an empty list.
+    pub fn captured(&self) -> Vec<(LogLevel, String)> {
+        match &self.captured {
+            Some(captured) => captured.lock().unwrap().iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Keep at most `max` records in a `capturing` logger, dropping the oldest ones, 10000 by
+    /// default. The records kept so far are trimmed with the next record.
+    pub fn set_max_tracked_messages(&self, max: usize) {
+        self.update_config(|config| config.max_tracked_messages = max);
+    }
+
+    /// Forget the records captured so far
+    pub fn clear_captured(&self) {
+        if let Some(captured) = &self.captured {
+            captured.lock().unwrap().clear();
+        }
+    }
+
+    /// Create a child logger that writes to the same log files as this one.
+    /// The child follows this logger's max level until `set_level` is called on it.
+    pub fn child(&'static self) -> LogUtil {
+        self.share(Some(self))
+    }
+
+    /// A logger writing to the same files and sinks as this one, with a copy of its config
+    fn share(&self, parent: Option<&'static LogUtil>) -> LogUtil {
+        LogUtil {
+            class_name: self.class_name,
+            out_log_file: self.out_log_file.clone(),
+            out_log_file_line_position: self.out_log_file_line_position.clone(),
+            out_log_date_file: self.out_log_date_file.clone(),
+            out_log_date_file_line_position: self.out_log_date_file_line_position.clone(),
+            out_log_date: Arc::clone(&self.out_log_date),
+            out_log_date_file_index: Arc::clone(&self.out_log_date_file_index),
+            level_files: Arc::clone(&self.level_files),
+            #[cfg(feature = "serde_json")]
+            json_file: Arc::clone(&self.json_file),
+            last_record: Arc::clone(&self.last_record),
+            level: AtomicUsize::new(LEVEL_INHERIT),
+            parent,
+            config: ArcSwap::new(self.config.load_full()),
+            log_dir: self.log_dir.clone(),
+            single_file_path: self.single_file_path.clone(),
+            dir_mode: self.dir_mode,
+            last_record_time: Mutex::new(None),
+            last_progress_render: Mutex::new(None),
+            pending_progress: Mutex::new(None),
+            last_progress_width: Mutex::new(0),
+            captures: Arc::clone(&self.captures),
+            captured: self.captured.clone(),
+            zone_trees: Arc::clone(&self.zone_trees),
+            record_callbacks: Arc::clone(&self.record_callbacks),
+            formatter: Arc::clone(&self.formatter),
+            redactor: Arc::clone(&self.redactor),
+            counts: Arc::clone(&self.counts),
+            recent: Arc::clone(&self.recent),
+            files_suspended: AtomicBool::new(false),
+            file_write_failures: AtomicUsize::new(0),
+            heartbeat: Mutex::new(None),
+            rollover_timer: Mutex::new(None),
+            #[cfg(all(unix, feature = "syslog"))]
+            syslog: self.syslog.clone(),
+            background: None,
+            last_flush: Mutex::new(Instant::now()),
+            clock: Arc::clone(&self.clock),
+        }
+    }
+
+    /// Override the max level of this logger only. The global `log` max level is not changed.
+    pub fn set_level(&self, level: LevelFilter) {
+        self.level.store(level as usize, Ordering::Relaxed);
+    }
+
+    /// Filter the records of `sink` by `level` instead of the logger's level, e.g. a console
+    /// showing warnings while the files get debug records. Records reach the logger while any
+    /// sink accepts them, so a sink may be more verbose than the logger's level.
+    pub fn set_sink_level(&self, sink: Sink, level: LevelFilter) {
+        self.update_config(|config| {
+            config.sink_levels.retain(|(s, _)| *s != sink);
+            config.sink_levels.push((sink, level));
+        });
+        raise_facade_level(level);
+    }
+
+    /// Only let records through whose target contains `filter`, e.g. `net::` while debugging
+    /// the network code, until it is called with `None`. The levels still apply. Meant to be
+    /// switched at runtime, e.g. from a signal handler.
+    pub fn set_target_filter(&self, filter: Option<String>) {
+        self.update_config(|config| config.target_filter = filter.clone());
+    }
+
+    /// Pad the level labels of the console and the files to one width, so the messages line up
+    /// in a column. `LevelAlign::None`, no padding, by default.
+    pub fn set_level_align(&self, align: LevelAlign) {
+        self.update_config(|config| config.level_align = align);
+    }
+
+    /// Show the levels as `LevelLabel::Short` (`WRN`) or `LevelLabel::Char` (`W`) labels on the
+    /// console and in the files, e.g. for dense logs. `LevelLabel::Full` (`WARN`) by default.
+    pub fn set_level_label(&self, label: LevelLabel) {
+        self.update_config(|config| config.level_label = label);
+    }
+
+    /// Let `sink` follow the logger's level again
+    pub fn inherit_sink_level(&self, sink: Sink) {
+        self.update_config(|config| config.sink_levels.retain(|(s, _)| *s != sink));
+    }
+
+    /// Drop the level override so this logger follows its parent again.
+    pub fn inherit_level(&self) {
+        self.level.store(LEVEL_INHERIT, Ordering::Relaxed);
+    }
+
+    /// The effective max level: the override if set, otherwise the parent's level,
+    /// otherwise the level from `RUST_LOG`.
+    pub fn level(&self) -> LevelFilter {
+        match self.level.load(Ordering::Relaxed) {
+            LEVEL_INHERIT => match self.parent {
+                Some(parent) => parent.level(),
+                None => Self::max_level(),
+            },
+            level => level_filter_from_usize(level),
+        }
+    }
+
+    /// Change the max level of all loggers without a level of their own at runtime, e.g. to
+    /// raise verbosity on SIGHUP. This also sets the `log` crate's max level, so the `log!`
+    /// macros follow. Until it is called the level comes from `RUST_LOG`.
+    pub fn set_max_level(level: LevelFilter) {
+        RUNTIME_MAX_LEVEL.store(level as usize, Ordering::Relaxed);
+        let config_max = level_filter_from_usize(CONFIG_MAX_LEVEL.load(Ordering::Relaxed));
+        log::set_max_level(facade_max_level(level, &ENV_TARGET_LEVELS).max(config_max));
+    }
+
+    /// Filter records whose target is `prefix` or one of its submodules by `level` instead of
+    /// the logger's level, like `RUST_LOG=prefix=level`. The most specific matching prefix wins.
+    pub fn set_target_level(&self, prefix: &str, level: LevelFilter) {
+        self.update_config(|config| {
+            config.target_levels.retain(|(p, _)| p != prefix);
+            config.target_levels.push((prefix.to_string(), level));
+        });
+        raise_facade_level(level);
+    }
+
+    /// The max level of loggers without a level of their own, see `set_max_level`
+    pub fn max_level() -> LevelFilter {
+        match RUNTIME_MAX_LEVEL.load(Ordering::Relaxed) {
+            LEVEL_INHERIT => *MAX_LOG_LEVEL,
+            level => level_filter_from_usize(level),
         }
     }
 
     pub fn set_class_name(&mut self, class_name: &'static str) {
         self.class_name = class_name;
     }
-}
 
-#[macro_export]
-#[deprecated]
-macro_rules! output_progress_log {
-    ($log_level:expr, $($arg:tt)*) => {{
-        if $log_level as u32 <= *$crate::my_log::MAX_LOG_LEVEL as u32 {
-            $crate::output!("\r");
-            $crate::output_log!($log_level, $($arg)*);
-            let _ = io::stdout().flush();
+    /// Show or hide the timestamp in the console and file output. Timestamps are shown by default;
+    /// hiding them gives stable output for snapshot tests.
+    pub fn set_show_timestamp(&self, show: bool) {
+        self.update_config(|config| config.show_timestamp = show);
+    }
+
+    /// Change the chrono strftime format of the timestamps, e.g. `"%H:%M:%S%.3f"`. The format
+    /// is checked by rendering a sample date first, an invalid specifier or a format that
+    /// renders nothing is rejected and the current format is kept.
+    pub fn try_set_timestamp_format(&self, format: &str) -> Result<(), FormatError> {
+        config::validate_timestamp_format(format)?;
+        self.update_config(|config| config.timestamp_format = format.to_string());
+        Ok(())
+    }
+
+    /// Set the text placed between the bracketed prefix and the message in the console and
+    /// file output, e.g. `"\t"` or `" | "`. The default is a single space.
+    pub fn set_field_separator(&self, separator: String) {
+        self.update_config(|config| config.field_separator = separator.clone());
+    }
+
+    /// Buffer the file writes and flush them at most once per `interval`, which saves a write
+    /// syscall per record under load. Buffered records are written by the first record after
+    /// the interval, by `Log::flush` and when the logger is dropped; errors are flushed right
+    /// away. `None`, the default, flushes after every record.
+    pub fn set_flush_interval(&self, interval: Option<Duration>) {
+        self.update_config(|config| config.flush_interval = interval);
+    }
+
+    /// Print warnings and errors to stderr instead of stdout, e.g. to keep them visible when
+    /// stdout is redirected. Info and lower levels stay on stdout. Off by default, all console
+    /// output goes to stdout.
+    pub fn set_warnings_to_stderr(&self, enabled: bool) {
+        self.update_config(|config| config.warnings_to_stderr = enabled);
+    }
+
+    /// Annotate every record with the time elapsed since the previous record of this logger,
+    /// e.g. `[+0.123s]`. The delta is global to the logger, not per thread, so records from
+    /// different threads are measured against each other. The first record shows `[+0.000s]`.
+    pub fn set_show_delta(&self, show: bool) {
+        self.update_config(|config| config.show_delta = show);
+    }
+
+    /// Render the deltas of `set_show_delta` as `DurationFormat::Iso8601`, e.g. `[+PT0.02S]`,
+    /// instead of seconds with millisecond precision
+    pub fn set_delta_format(&self, format: DurationFormat) {
+        self.update_config(|config| config.delta_format = format);
+    }
+
+    /// Also write every record to a file of its level next to the latest file, e.g.
+    /// `log/<class_name>/<class_name>_ERROR.log` to read only the errors. The level files are
+    /// opened and rolled over like the latest file and follow its `MainLogMode`. Only loggers
+    /// with the latest and the dated file split, not `single_file` or `with_writer` ones.
+    /// Off by default.
+    pub fn set_split_by_level(&self, split: bool) {
+        self.update_config(|config| config.split_by_level = split);
+    }
+
+    /// Collapse runs of identical records, same level and message, in the files: the first one
+    /// is written, the repeats only counted. When a different record arrives or on `flush()`
+    /// a `...last message repeated N times` line of the same level is written in the format
+    /// of the files. The console still shows every record. Off by default.
+    pub fn set_dedup(&self, dedup: bool) {
+        self.update_config(|config| config.dedup = dedup);
+    }
+
+    /// Show where errors were logged on the console, e.g. `[src/db.rs:42] `. On by default in
+    /// debug builds and off in release builds, where it can be turned on to debug an incident
+    /// without a debug build.
+    pub fn set_show_location(&self, show: bool) {
+        self.update_config(|config| config.show_location = show);
+    }
+
+    /// Also rewrite every progress update in place in the dated file, instead of only writing
+    /// the final one. Records logged while a progress is running then follow a partial
+    /// progress line in the dated file. Off by default.
+    pub fn set_progress_to_date_file(&self, enabled: bool) {
+        self.update_config(|config| config.progress_to_date_file = enabled);
+    }
+
+    /// Start every record in the files with the module path it was logged from, e.g.
+    /// `[app::db] query done`, errors with the line too: `[app::db:42] query failed`. The
+    /// console is not affected. Off by default.
+    pub fn set_show_source(&self, show: bool) {
+        self.update_config(|config| config.show_source = show);
+    }
+
+    /// Annotate every record with the name of the logging thread, e.g. `[worker-3]`, or with
+    /// its `ThreadId` when the thread is unnamed. Off by default.
+    pub fn set_show_thread(&self, show: bool) {
+        self.update_config(|config| config.show_thread = show);
+    }
+
+    /// Annotate records logged from inside a tokio task with the task id, e.g. `[task 12]`.
+    /// In async code the thread says little about where a record came from, the task does.
+    /// Records logged outside of a task are not annotated.
+    #[cfg(feature = "tokio")]
+    pub fn set_show_task_id(&self, show: bool) {
+        self.update_config(|config| config.show_task_id = show);
+    }
+
+    /// Write at most one `output_progress_msg` update per `interval` to the console and the
+    /// files. Updates in between are coalesced, the latest one is written by `flush()` unless
+    /// a later update was written first. The final `is_process_stop` update is always
+    /// written. The default of zero writes every update.
+    pub fn set_progress_min_update_interval(&self, interval: Duration) {
+        self.update_config(|config| config.progress_min_update_interval = interval);
+    }
+
+    /// Keep records whose target is `prefix` or one of its submodules (`prefix::...`) off the
+    /// console. They are still written to the files. Useful for noisy subsystems such as
+    /// polling loops. Captures still get them, see `Sink::Capture`.
+    pub fn silence_console_for_target(&self, prefix: &str) {
+        self.update_config(|config| config.console_silenced_targets.push(prefix.to_string()));
+    }
+
+    /// Enable or disable ANSI colors on the console. By default colors are only used when
+    /// stdout is a terminal, so piped output and CI logs stay plain text. This also applies to
+    /// the `output_*_log` macros, which color through the global `colored` override.
+    /// Without the `color` feature the console is never colored.
+    pub fn set_color_enabled(&self, enabled: bool) {
+        #[cfg(feature = "color")]
+        colored::control::set_override(enabled);
+        self.update_config(|config| config.color = enabled);
+    }
+
+    /// Style the levels on the console with `scheme` instead of `ColorScheme::default()`, e.g.
+    /// for a light terminal theme. Like `set_color_enabled` this applies to every logger and the
+    /// `output_*_log` macros.
+    #[cfg(feature = "color")]
+    pub fn set_color_scheme(&self, scheme: ColorScheme) {
+        style::set_color_scheme(scheme);
+    }
+
+    /// Show records whose target is `prefix` or one of its submodules with a `[target]` segment
+    /// in `color` on the console, e.g. `db` in blue and `http` in magenta. The longest matching
+    /// prefix wins. The segment is not colored when coloring is disabled, e.g. with `NO_COLOR`.
+    #[cfg(feature = "color")]
+    pub fn set_target_color(&self, prefix: &str, color: Color) {
+        self.update_config(|config| {
+            config.target_colors.retain(|(p, _)| p != prefix);
+            config.target_colors.push((prefix.to_string(), color));
+        });
+    }
+
+    /// Replace every match of `pattern` in messages with `replacement` before they reach the
+    /// console, the files or any other sink, e.g. to keep tokens out of the logs. The
+    /// replacement may refer to capture groups (`$1`). Redactions run once per record in the
+    /// order they were added.
+    pub fn add_redaction(&self, pattern: Regex, replacement: &str) {
+        self.update_config(|config| {
+            config.redactions.push(Redaction {
+                pattern: pattern.clone(),
+                replacement: replacement.to_string(),
+            })
+        });
+    }
+
+    /// Scrub every message with `redactor` before it reaches the console, the files or any
+    /// other sink, after the `add_redaction` patterns, e.g. to mask emails with code a regex
+    /// can't express. Progress updates and field values are scrubbed too. It runs before the
+    /// console colors are added, so it never sees escape codes.
+    pub fn set_redactor(&self, redactor: Redactor) {
+        self.redactor.store(Some(Arc::new(redactor)));
+    }
+
+    /// Stop scrubbing the messages after `set_redactor`
+    pub fn clear_redactor(&self) {
+        self.redactor.store(None);
+    }
+
+    /// `message` after the redaction patterns and the redactor, borrowed when unchanged
+    fn redact<'a>(&self, config: &LogConfig, message: &'a str) -> Cow<'a, str> {
+        let message = config.redact(message);
+        let Some(redactor) = self.redactor.load_full() else {
+            return message;
+        };
+        let redacted = match redactor(&message) {
+            Cow::Borrowed(unchanged) if unchanged == message => None,
+            redacted => Some(redacted.into_owned()),
+        };
+        redacted.map_or(message, Cow::Owned)
+    }
+
+    /// Whether file output was suspended after repeated write failures
+    pub fn files_suspended(&self) -> bool {
+        match &self.background {
+            Some(background) => background.logger.files_suspended(),
+            None => self.files_suspended.load(Ordering::Relaxed),
         }
-    }};
+    }
+
+    /// Resume file output after it was suspended because of write failures, e.g. once the
+    /// log volume is writable again. If writes keep failing it is suspended again.
+    pub fn recover_files(&self) {
+        if let Some(background) = &self.background {
+            background.logger.recover_files();
+        }
+        self.file_write_failures.store(0, Ordering::Relaxed);
+        self.files_suspended.store(false, Ordering::Relaxed);
+    }
+
+    /// A snapshot of the current configuration
+    pub fn config(&self) -> Arc<LogConfig> {
+        self.config.load_full()
+    }
+
+    /// Replace the whole configuration in one atomic step. Records logged concurrently are
+    /// formatted either entirely with the old or entirely with the new configuration.
+    pub fn reconfigure(&self, config: LogConfig) {
+        self.config.store(Arc::new(config));
+    }
+
+    fn update_config(&self, update: impl Fn(&mut LogConfig)) {
+        self.config.rcu(|config| {
+            let mut config = LogConfig::clone(config);
+            update(&mut config);
+            config
+        });
+    }
+
+    /// Start teeing records to the file at `path` in addition to the normal outputs.
+    /// The file is truncated first. Capturing ends when the returned handle is stopped or
+    /// dropped, which also flushes the capture file.
+    pub fn start_capture(&self, path: impl AsRef<Path>) -> io::Result<CaptureHandle> {
+        CaptureSinks::start(&self.captures, path.as_ref())
+    }
+
+    /// Additionally write every record to a dated file tree `log/<class_name>/<name>/` whose
+    /// timestamps, file dates and rollover follow the time zone `offset`, e.g. `"utc"` at
+    /// `+00:00` and `"jst"` at `+09:00`. Several trees can be added. A logger without log
+    /// files ignores this.
+    pub fn add_timezone_tree(&self, name: &str, offset: FixedOffset) -> io::Result<()> {
+        if self.out_log_date_file.is_none() {
+            return Ok(());
+        }
+        let dir = self.log_dir.join(name);
+        try_create_log_dir(&dir, self.dir_mode)?;
+        self.zone_trees
+            .lock()
+            .unwrap()
+            .push(ZoneTree::new(self.class_name, dir, offset));
+        Ok(())
+    }
+
+    /// Continue the dated file in numbered parts `<class_name>_YYYYMMDD.1.log`,
+    /// `<class_name>_YYYYMMDD.2.log`, ... whenever it grows past `max_bytes`, in addition to
+    /// the daily rollover. `tail_today` and `read_today` read the current part. `None`, the
+    /// default, never rotates by size.
+    pub fn set_max_file_bytes(&self, max_bytes: Option<u64>) {
+        self.update_config(|config| config.max_file_bytes = max_bytes);
+    }
+
+    /// Delete dated log files, including rotated parts and `.log.gz` files, that are more than
+    /// `max_days` days old. The class directory is cleaned up at every daily rollover.
+    /// `None`, the default, keeps all files.
+    pub fn set_max_retained_days(&self, max_days: Option<u32>) {
+        self.update_config(|config| config.max_retained_days = max_days);
+    }
+
+    /// Compress the previous day's dated files, including rotated parts, to `.log.gz` when
+    /// the logs roll over to a new day. The compression runs on a background thread and a
+    /// file is only removed once its archive is complete. Off by default.
+    #[cfg(feature = "flate2")]
+    pub fn set_compress_rolled(&self, compress: bool) {
+        self.update_config(|config| config.compress_rolled = compress);
+    }
+
+    /// Keep at most `max` time zone tree files open at once. When a record needs another file,
+    /// the least recently written one is closed and reopened on demand later, which avoids
+    /// running out of file descriptors with many trees. `None`, the default, keeps all open.
+    /// Only the time zone trees are capped: the main, dated, per-level and JSON files are a
+    /// fixed handful and always stay open.
+    pub fn set_max_open_zone_files(&self, max: Option<usize>) {
+        self.update_config(|config| config.max_open_zone_files = max);
+    }
+
+    /// Render timestamps in `time_zone` instead of the local time zone, e.g. `TimeZoneMode::Utc`
+    /// to correlate logs of machines in different zones. The files roll over on the date of
+    /// this zone, so a dated file only holds that day's records; when the date differs from
+    /// the current file's, the next record rolls over. Prefer `LogUtilBuilder::time_zone` to
+    /// name the first file correctly.
+    pub fn set_time_zone(&self, time_zone: TimeZoneMode) {
+        self.update_config(|config| config.time_zone = time_zone);
+    }
+
+    /// Choose the layout of the records in the log files. With `Format::Json` every record is
+    /// one JSON object per line for log pipelines, the console stays human-readable unless
+    /// `set_json_console` is enabled. The default is `Format::Text`.
+    pub fn set_format(&self, format: Format) {
+        self.update_config(|config| config.format = format);
+    }
+
+    /// Write every record to `<class_name>.jsonl` next to the latest file too, as one JSON
+    /// object per line like `Format::Json`, while the log files stay human-readable. The JSON
+    /// file is opened and rolled over like the latest file and follows its `MainLogMode`, so
+    /// with the default it only holds the current day. Only loggers with a dated file write
+    /// it. Off by default.
+    #[cfg(feature = "serde_json")]
+    pub fn set_json_file(&self, json_file: bool) {
+        self.update_config(|config| config.json_file = json_file);
+    }
+
+    /// Print the same JSON lines as the files to the console when the format is `Format::Json`
+    #[cfg(feature = "serde_json")]
+    pub fn set_json_console(&self, enabled: bool) {
+        self.update_config(|config| config.json_console = enabled);
+    }
+
+    /// Log `bytes` as a `hexdump -C` style dump: a `label (N bytes)` line followed by one
+    /// offset/hex/ASCII row per 16 bytes. The dump is a single record, so it is skipped as a
+    /// whole when `level` is filtered out.
+    pub fn hexdump(&self, level: Level, label: &str, bytes: &[u8]) {
+        let metadata = Metadata::builder()
+            .level(level)
+            .target(self.class_name)
+            .build();
+        if !self.enabled(&metadata) {
+            return;
+        }
+        let dump = format_hexdump(bytes);
+        self.log(
+            &Record::builder()
+                .metadata(metadata)
+                .args(format_args!("{} ({} bytes)\n{}", label, bytes.len(), dump))
+                .build(),
+        );
+    }
+
+    /// Log `msg` at `level` every `interval` from a background thread, e.g. as a liveness signal
+    /// for monitoring. The heartbeats are ordinary records and go to all sinks. Enabling it
+    /// again replaces the previous heartbeat. It runs until `disable_heartbeat` is called or
+    /// the `LogGuard` returned by `bootstrap` is dropped.
+    pub fn enable_heartbeat(&'static self, interval: Duration, level: Level, msg: &str) {
+        let msg = msg.to_string();
+        let heartbeat = Heartbeat::spawn(interval, move || {
+            self.log(
+                &Record::builder()
+                    .level(level)
+                    .target(self.class_name)
+                    .args(format_args!("{}", msg))
+                    .build(),
+            )
+        });
+        if let Some(previous) = self.heartbeat.lock().unwrap().replace(heartbeat) {
+            previous.stop();
+        }
+    }
+
+    /// Stop the heartbeat started by `enable_heartbeat`, if any
+    pub fn disable_heartbeat(&self) {
+        if let Some(heartbeat) = self.heartbeat.lock().unwrap().take() {
+            heartbeat.stop();
+        }
+    }
+
+    /// Roll the log files over from a background thread shortly after each midnight, instead of
+    /// only when the first record of the new day arrives. A service that is quiet overnight
+    /// then writes its first morning record to the file of the new day, and the retention and
+    /// compression of the previous day run at midnight. The midnight is that of the configured
+    /// `time_zone`, read from the logger's clock. Starting it again replaces the previous
+    /// timer. A logger without a dated file, e.g. a console-only or a `single_file` logger, has
+    /// nothing to roll over and starts no thread.
+    pub fn start_rollover_timer(&'static self) {
+        if self.out_log_date_file.is_none() {
+            return;
+        }
+        let timer = Heartbeat::spawn_with(
+            move || {
+                let now = self.config.load().time_zone.convert(&self.clock.now());
+                // The end of the day of the open files, already past when they are stale
+                let date = *self.out_log_date.lock().unwrap();
+                let midnight = date.succ_opt().unwrap().and_time(NaiveTime::MIN);
+                let wait = (midnight - now.naive_local()).to_std().unwrap_or_default();
+                (wait + ROLLOVER_TIMER_DELAY).min(ROLLOVER_TIMER_MAX_WAIT)
+            },
+            move || {
+                let config = self.config.load();
+                let now = config.time_zone.convert(&self.clock.now());
+                let (out_log_date_locked, rolled) = self.ensure_current_day(&config, &now);
+                drop(out_log_date_locked);
+                self.track_file_failure(rolled);
+            },
+        );
+        if let Some(previous) = self.rollover_timer.lock().unwrap().replace(timer) {
+            previous.stop();
+        }
+    }
+
+    /// Stop the thread started by `start_rollover_timer`, if any. Rollovers still happen when
+    /// the next record arrives.
+    pub fn stop_rollover_timer(&self) {
+        if let Some(timer) = self.rollover_timer.lock().unwrap().take() {
+            timer.stop();
+        }
+    }
+
+    /// Register `callback` to be invoked with every record that passes the level filter, e.g. to
+    /// forward errors to an alerting system. The `LogEvent` has the level, target, message and
+    /// timestamp of the record. Callbacks run synchronously on the logging thread, after the
+    /// record was written to the console and the files and without holding any of the logger's
+    /// locks, so a callback may log itself. Keep them cheap.
+    pub fn on_record(&self, callback: Box<dyn Fn(&LogEvent) + Send + Sync>) {
+        let callback: RecordCallback = Arc::from(callback);
+        self.record_callbacks.rcu(|callbacks| {
+            let mut callbacks = Vec::clone(callbacks);
+            callbacks.push(Arc::clone(&callback));
+            callbacks
+        });
+    }
+
+    /// Render every record with `formatter` instead of the configured layout, in the latest and
+    /// dated files, the capture files and on the console. The console colors the returned line
+    /// by level when coloring is on. The event's message is already redacted, and
+    /// `LogEvent::render_default` gives the standard line to wrap. Progress updates and the
+    /// time zone trees keep the configured layout.
+    pub fn set_formatter(&self, formatter: RecordFormatter) {
+        self.formatter.store(Some(Arc::new(formatter)));
+    }
+
+    /// Go back to the configured layout after `set_formatter`
+    pub fn clear_formatter(&self) {
+        self.formatter.store(None);
+    }
+
+    /// How many records of each level passed the level filter so far, including progress
+    /// updates and the records of child loggers, e.g. to export the errors of a service to a
+    /// dashboard without reading the files
+    pub fn counts(&self) -> LevelCounts {
+        self.counts.snapshot()
+    }
+
+    /// Keep the last `capacity` formatted records in memory for `recent`, e.g. 200 to attach
+    /// them to an error report. Lines above the capacity are dropped, oldest first. Zero, the
+    /// default, keeps none.
+    pub fn set_recent_capacity(&self, capacity: usize) {
+        self.recent.set_capacity(capacity);
+    }
+
+    /// The last records, oldest first, as written to the files but independent of them. Only
+    /// filled after `set_recent_capacity`, the records of child loggers included.
+    pub fn recent(&self) -> Vec<String> {
+        self.recent.snapshot()
+    }
+
+    /// Start counting from zero again, e.g. at the start of each reporting window. Returns the
+    /// counts until now, so no record is missed between reading and resetting.
+    pub fn reset_counts(&self) -> LevelCounts {
+        self.counts.reset()
+    }
+
+    /// Return the last `n` lines of the current dated log file, oldest first.
+    /// A logger without log files returns an empty list.
+    ///
+    /// Like `read_today`, bytes that are not valid UTF-8 are replaced with `U+FFFD`.
+    pub fn tail_today(&self, n: usize) -> io::Result<Vec<String>> {
+        let _ = self.flush_files();
+        match self.current_date_log_path() {
+            Some(path) => history::read_last_lines(&path, n),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Return the whole content of the current dated log file.
+    /// A logger without log files returns an empty string.
+    ///
+    /// Reading is lossy: bytes that are not valid UTF-8 (e.g. written by `write_raw_line`)
+    /// are replaced with `U+FFFD` instead of failing the read.
+    pub fn read_today(&self) -> io::Result<String> {
+        let _ = self.flush_files();
+        match self.current_date_log_path() {
+            Some(path) => history::read_to_string_lossy(&path),
+            None => Ok(String::new()),
+        }
+    }
+
+    /// The days with a dated log file `<class_name>_YYYYMMDD.log` in the log directory, oldest
+    /// first, e.g. to offer them to `read_date`. Rotated parts and compressed files are not
+    /// listed, neither are files whose name has no valid date. A logger without a dated file
+    /// returns an empty list.
+    pub fn list_log_dates(&self) -> io::Result<Vec<NaiveDate>> {
+        if self.out_log_date_file.is_none() {
+            return Ok(Vec::new());
+        }
+        let mut dates = Vec::new();
+        for entry in fs::read_dir(&self.log_dir)? {
+            let name = entry?.file_name();
+            if let Some((date, ".log")) = name
+                .to_str()
+                .and_then(|name| split_date_file_name(self.class_name, name))
+            {
+                dates.push(date);
+            }
+        }
+        dates.sort();
+        Ok(dates)
+    }
+
+    /// Return the whole content of the dated log file of `date`. When only the compressed
+    /// `<class_name>_YYYYMMDD.log.gz` exists it is decompressed transparently (requires the
+    /// `flate2` feature). Reading is lossy like `read_today`. A logger without log files
+    /// returns an empty string.
+    pub fn read_date(&self, date: NaiveDate) -> io::Result<String> {
+        let _ = self.flush_files();
+        if self.out_log_date_file.is_none() {
+            return Ok(String::new());
+        }
+        let path = self
+            .log_dir
+            .join(format!("{}_{}.log", self.class_name, date.format("%Y%m%d")));
+        if path.exists() {
+            history::read_to_string_lossy(&path)
+        } else {
+            history::read_to_string_lossy(&path.with_extension("log.gz"))
+        }
+    }
+
+    /// Write `bytes` followed by a line break to the log files as-is, without a prefix.
+    /// The bytes don't have to be valid UTF-8 and nothing is printed to the console.
+    pub fn write_raw_line(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut line = bytes.to_vec();
+        line.push(b'\n');
+        self.write_line_to_files(&self.config.load(), &line)
+    }
+
+    /// Write `line` to the latest file and the dated file. Both files stay locked for the whole
+    /// write and the dated file is skipped when writing the latest file failed, so the two files
+    /// keep the same records in the same order. With `MainLogMode::LatestLine` the latest file
+    /// is emptied first, unless it is a writer that can't seek.
+    fn write_line_to_files(&self, config: &LogConfig, line: &[u8]) -> io::Result<()> {
+        let latest_line_only = config.main_log_mode == MainLogMode::LatestLine;
+        let mut sinks = Vec::with_capacity(2);
+        for (write_file, line_position, replace) in [
+            (
+                &self.out_log_file,
+                &self.out_log_file_line_position,
+                latest_line_only,
+            ),
+            (
+                &self.out_log_date_file,
+                &self.out_log_date_file_line_position,
+                false,
+            ),
+        ] {
+            if let (Some(write_file), Some(line_position)) = (write_file, line_position) {
+                sinks.push((write_file.lock().unwrap(), line_position, replace));
+            }
+        }
+        for (write_file, line_position, replace) in sinks.iter_mut() {
+            if *replace && write_file.seek(io::SeekFrom::Start(0)).is_ok() {
+                write_file.set_len(0)?;
+            }
+            write_file.write_all(line)?;
+            // modify the position at the beginning of the line, a progress update replaces the
+            // whole latest line file
+            let mut lp = line_position.lock().unwrap();
+            *lp = if *replace {
+                0
+            } else {
+                write_file.stream_position().unwrap_or_default()
+            };
+        }
+        Ok(())
+    }
+
+    /// Write `line` to the file of `level` when splitting by level. Called with the date lock
+    /// held, so the level files roll over together with the others.
+    fn write_level_file(&self, config: &LogConfig, level: Level, line: &[u8]) -> io::Result<()> {
+        if !config.split_by_level || self.out_log_date_file.is_none() {
+            return Ok(());
+        }
+        let mut level_files = self.level_files.lock().unwrap();
+        let write_file = match level_files.entry(level) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = self
+                    .log_dir
+                    .join(format!("{}_{}.log", self.class_name, level));
+                let file = open_main_log(&path, config.main_log_mode)
+                    .map_err(|e| log_file_error(&path, e))?;
+                entry.insert(file.into())
+            }
+        };
+        if config.main_log_mode == MainLogMode::LatestLine
+            && write_file.seek(io::SeekFrom::Start(0)).is_ok()
+        {
+            write_file.set_len(0)?;
+        }
+        write_file.write_all(line)
+    }
+
+    /// Write `event` as a JSON line to `<class_name>.jsonl` with `set_json_file`. Called with
+    /// the date lock held, so the file rolls over together with the others.
+    #[cfg(feature = "serde_json")]
+    fn write_json_file(
+        &self,
+        config: &LogConfig,
+        event: &LogEvent,
+        now_str: &str,
+    ) -> io::Result<()> {
+        if !config.json_file || self.out_log_date_file.is_none() {
+            return Ok(());
+        }
+        let mut json_file = self.json_file.lock().unwrap();
+        let write_file = match json_file.as_mut() {
+            Some(write_file) => write_file,
+            None => {
+                let path = self.log_dir.join(format!("{}.jsonl", self.class_name));
+                let file = open_main_log(&path, config.main_log_mode)
+                    .map_err(|e| log_file_error(&path, e))?;
+                json_file.insert(file.into())
+            }
+        };
+        if config.main_log_mode == MainLogMode::LatestLine
+            && write_file.seek(io::SeekFrom::Start(0)).is_ok()
+        {
+            write_file.set_len(0)?;
+        }
+        write_file.write_all((event.render_json(now_str) + "\n").as_bytes())
+    }
+
+    /// Path of the latest log file `<class_name>.log`, or the file of a `single_file` logger.
+    /// A logger without log files, without the latest file (see `LogUtilBuilder::main_log`) or
+    /// writing to a `with_writer` writer returns `None`.
+    pub fn current_log_path(&self) -> Option<PathBuf> {
+        if self.single_file_path.is_some() {
+            return self.single_file_path.clone();
+        }
+        if let LogSink::Writer(_) = *self.out_log_file.as_ref()?.lock().unwrap() {
+            return None;
+        }
+        Some(self.log_dir.join(format!("{}.log", self.class_name)))
+    }
+
+    /// Path of the dated log file currently written, following rollovers and size rotations,
+    /// e.g. `log/<class_name>/<class_name>_YYYYMMDD.1.log`. A logger without a dated file,
+    /// including a `single_file` logger, returns `None`.
+    pub fn current_date_log_path(&self) -> Option<PathBuf> {
+        self.out_log_date_file.as_ref()?;
+        let date = *self.out_log_date.lock().unwrap();
+        let index = self.out_log_date_file_index.load(Ordering::Relaxed);
+        Some(
+            self.log_dir
+                .join(date_file_name(self.class_name, date, index)),
+        )
+    }
+
+    /// Paths of the dated file of `date` and of its rotated parts up to the current part
+    #[cfg(feature = "flate2")]
+    fn date_file_paths(&self, dir: &Path, date: NaiveDate) -> Vec<PathBuf> {
+        (0..=self.out_log_date_file_index.load(Ordering::Relaxed))
+            .map(|index| dir.join(date_file_name(self.class_name, date, index)))
+            .collect()
+    }
+
+    /// Whether `event` repeats the last record and is only counted. Otherwise the summary of
+    /// the repeats before it is written and `event` becomes the last record. Called under the
+    /// date lock, so the summary goes to the files of the current day.
+    fn dedup_record(
+        &self,
+        config: &LogConfig,
+        event: &LogEvent,
+        message: &str,
+        now_str: &str,
+    ) -> io::Result<bool> {
+        let mut last_record = self.last_record.lock().unwrap();
+        if let Some(last) = last_record.as_mut() {
+            if config.dedup && last.event.level == event.level && last.message == message {
+                last.repeated += 1;
+                return Ok(true);
+            }
+        }
+        let summary = last_record.take();
+        if config.dedup {
+            *last_record = Some(RepeatedRecord {
+                event: event.clone(),
+                message: message.to_string(),
+                repeated: 0,
+            });
+        }
+        drop(last_record);
+        match summary {
+            Some(summary) => self.write_repeat_summary(config, summary, event.time, now_str),
+            None => Ok(()),
+        }
+        .map(|()| false)
+    }
+
+    fn write_repeat_summary(
+        &self,
+        config: &LogConfig,
+        last: RepeatedRecord,
+        time: DateTime<Local>,
+        now_str: &str,
+    ) -> io::Result<()> {
+        if last.repeated == 0 {
+            return Ok(());
+        }
+        let mut summary = last.event;
+        summary.time = time;
+        summary.message = format!("...last message repeated {} times", last.repeated);
+        summary.kv.clear();
+        let line = match self.formatter.load().as_ref() {
+            Some(formatter) => formatter(&summary) + "\n",
+            None => config.format_file_line(&summary, now_str, &summary.message),
+        };
+        self.write_line_to_files(config, line.as_bytes())
+            .and_then(|()| self.write_level_file(config, summary.level, line.as_bytes()))
+    }
+
+    /// Write the summary of the repeats of the last record, leaving it the last record
+    fn flush_repeat_summary(&self) {
+        if self.files_suspended.load(Ordering::Relaxed) {
+            return;
+        }
+        let config = self.config.load();
+        let time = self.clock.now();
+        let now = config.time_zone.convert(&time);
+        let (out_log_date_locked, rolled) = self.ensure_current_day(&config, &now);
+        let write_result = rolled
+            .and_then(|()| self.write_pending_repeats(&config, time, &config.format_time(&now)));
+        drop(out_log_date_locked);
+        self.track_file_write(write_result);
+    }
+
+    /// `flush_repeat_summary` with the date lock held
+    fn write_pending_repeats(
+        &self,
+        config: &LogConfig,
+        time: DateTime<Local>,
+        now_str: &str,
+    ) -> io::Result<()> {
+        let mut last_record = self.last_record.lock().unwrap();
+        let Some(last) = last_record.as_mut().filter(|last| last.repeated > 0) else {
+            return Ok(());
+        };
+        let summary = last.clone();
+        last.repeated = 0;
+        drop(last_record);
+        self.write_repeat_summary(config, summary, time, now_str)
+    }
+
+    /// Write the buffered records of both files, the level files and the JSON file
+    fn flush_files(&self) -> io::Result<()> {
+        for write_file in [&self.out_log_file, &self.out_log_date_file]
+            .into_iter()
+            .flatten()
+        {
+            write_file.lock().unwrap().flush()?;
+        }
+        for write_file in self.level_files.lock().unwrap().values_mut() {
+            write_file.flush()?;
+        }
+        #[cfg(feature = "serde_json")]
+        if let Some(write_file) = self.json_file.lock().unwrap().as_mut() {
+            write_file.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flush after every record without a `flush_interval`, otherwise once the interval passed
+    /// since the last flush. Errors are always flushed right away.
+    fn flush_files_if_due(&self, config: &LogConfig, level: Level) -> io::Result<()> {
+        if let (Some(interval), false) = (config.flush_interval, level == Level::Error) {
+            let mut last_flush = self.last_flush.lock().unwrap();
+            if last_flush.elapsed() < interval {
+                return Ok(());
+            }
+            *last_flush = Instant::now();
+        }
+        self.flush_files()
+    }
+
+    /// Roll the files over when `now` is on another day than the open files. The date lock is
+    /// held from the check until the new files are in place and returned to the caller, so of
+    /// several threads crossing midnight together only the first reopens (and truncates) the
+    /// latest file, the others find the date already current. When the rollover failed the
+    /// old files stay open and the next call tries again.
+    fn ensure_current_day(
+        &self,
+        config: &LogConfig,
+        now: &DateTime<FixedOffset>,
+    ) -> (MutexGuard<'_, NaiveDate>, io::Result<()>) {
+        let mut out_log_date_locked = self.out_log_date.lock().unwrap();
+        let rolled = if now.date_naive() != *out_log_date_locked {
+            self.roll_over(config, now, &mut out_log_date_locked)
+        } else {
+            Ok(())
+        };
+        (out_log_date_locked, rolled)
+    }
+
+    /// Count a failed rollover or write like `track_file_write`, returning whether it failed.
+    /// Success doesn't reset the count, e.g. a rollover wrote no record yet.
+    fn track_file_failure(&self, rolled: io::Result<()>) -> bool {
+        let failed = rolled.is_err();
+        if failed {
+            self.track_file_write(rolled);
+        }
+        failed
+    }
+
+    /// Move every file of the logger to the day of `now`, with the date lock `date` held: the
+    /// repeats of the last record are summed up in the old files, then the latest and the
+    /// dated file, part 0, and the open level files are reopened, the line positions and the
+    /// part index start over for the new files. When the directory or the latest or dated file
+    /// can't be opened, e.g. on a full or read-only volume, the old files are kept and `date`
+    /// is not changed.
+    fn roll_over(
+        &self,
+        config: &LogConfig,
+        now: &DateTime<FixedOffset>,
+        date: &mut NaiveDate,
+    ) -> io::Result<()> {
+        // Without a dated file nothing rolls over, the latest file is optional
+        let Some(write_date_file) = self.out_log_date_file.as_ref() else {
+            return Ok(());
+        };
+        if !self.files_suspended.load(Ordering::Relaxed) {
+            let time = now.with_timezone(&Local);
+            let write_result = self.write_pending_repeats(config, time, &config.format_time(now));
+            self.track_file_failure(write_result);
+        }
+        // Buffered lines must reach the old files before a new handle truncates them
+        let _ = self.flush_files();
+        let log_dir = &self.log_dir;
+        try_create_log_dir(log_dir, self.dir_mode)?;
+        // The dated file is opened first, opening the latest file may already truncate it
+        let out_date_file_path =
+            log_dir.join(format!("{}_{}.log", self.class_name, now.format("%Y%m%d")).as_str());
+        let mut out_date_file = OpenOptions::new()
+            .write(true)
+            .read(true)
+            .create(true)
+            .truncate(false)
+            .open(&out_date_file_path)
+            .map_err(|e| log_file_error(&out_date_file_path, e))?;
+        // Jump to the end of the file before starting to write
+        let date_file_len = out_date_file.seek(io::SeekFrom::End(0)).unwrap_or_default();
+        let mut out_file_len = 0;
+        if let Some(write_file) = &self.out_log_file {
+            let out_file_path = log_dir.join(format!("{}.log", self.class_name).as_str());
+            let mut out_file = open_main_log(&out_file_path, config.main_log_mode)
+                .map_err(|e| log_file_error(&out_file_path, e))?;
+            out_file_len = out_file.stream_position().unwrap_or_default();
+            *write_file.lock().unwrap() = out_file.into();
+        }
+        *write_date_file.lock().unwrap() = out_date_file.into();
+        // A level file that can't be reopened is tried again with the next record of its level
+        self.level_files
+            .lock()
+            .unwrap()
+            .retain(|level, level_file| {
+                let path = log_dir.join(format!("{}_{}.log", self.class_name, level));
+                match open_main_log(&path, config.main_log_mode) {
+                    Ok(file) => {
+                        *level_file = file.into();
+                        true
+                    }
+                    Err(_) => false,
+                }
+            });
+        // Like a level file, a JSON file that can't be reopened is tried with the next record
+        #[cfg(feature = "serde_json")]
+        {
+            let mut json_file = self.json_file.lock().unwrap();
+            if json_file.is_some() {
+                let path = log_dir.join(format!("{}.jsonl", self.class_name));
+                *json_file = open_main_log(&path, config.main_log_mode)
+                    .ok()
+                    .map(Into::into);
+            }
+        }
+        // A progress line continues at the end of the new files, not at the old position
+        for (line_position, position) in [
+            (&self.out_log_file_line_position, out_file_len),
+            (&self.out_log_date_file_line_position, date_file_len),
+        ] {
+            if let Some(line_position) = line_position {
+                *line_position.lock().unwrap() = position;
+            }
+        }
+        // The previous day's files are closed once replaced and can be compressed
+        #[cfg(feature = "flate2")]
+        let rolled_files = config
+            .compress_rolled
+            .then(|| self.date_file_paths(log_dir, *date));
+        *date = now.date_naive();
+        self.out_log_date_file_index.store(0, Ordering::Relaxed);
+        if let Some(max_days) = config.max_retained_days {
+            remove_expired_date_files(log_dir, self.class_name, now.date_naive(), max_days);
+        }
+        #[cfg(feature = "flate2")]
+        if let Some(rolled_files) = rolled_files {
+            compress::compress_in_background(rolled_files);
+        }
+        Ok(())
+    }
+
+    /// Before writing a record, continue the dated file in the next numbered part
+    /// `<class_name>_YYYYMMDD.<n>.log` when it grew past `max_file_bytes`. The size is the line
+    /// position kept after the last write, so no extra syscall is needed per record. Called
+    /// with the date lock held.
+    fn rotate_date_file_if_full(&self, config: &LogConfig, date: NaiveDate) -> io::Result<()> {
+        let (Some(max_file_bytes), Some(write_file), Some(line_position)) = (
+            config.max_file_bytes,
+            self.out_log_date_file.as_ref(),
+            self.out_log_date_file_line_position.as_ref(),
+        ) else {
+            return Ok(());
+        };
+        let mut write_file = write_file.lock().unwrap();
+        let mut line_position = line_position.lock().unwrap();
+        if *line_position <= max_file_bytes {
+            return Ok(());
+        }
+        let log_dir = &self.log_dir;
+        let mut index = self.out_log_date_file_index.load(Ordering::Relaxed);
+        // Skip parts that are already full, e.g. from before a restart
+        let (file, len) = loop {
+            index += 1;
+            let path = log_dir.join(date_file_name(self.class_name, date, index));
+            let mut file = OpenOptions::new()
+                .write(true)
+                .read(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .map_err(|e| log_file_error(&path, e))?;
+            let len = file.seek(io::SeekFrom::End(0))?;
+            if len <= max_file_bytes {
+                break (file, len);
+            }
+        };
+        *write_file = file.into();
+        *line_position = len;
+        self.out_log_date_file_index.store(index, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[macro_export]
+#[deprecated]
+macro_rules! output_progress_log {
+    ($log_level:expr, $($arg:tt)*) => {{
+        let log_level = $crate::log_level::LogLevel::from($log_level);
+        if log_level.is_enabled(*$crate::log_util::MAX_LOG_LEVEL) {
+            $crate::output!("\r");
+            $crate::output_log!(log_level, $($arg)*);
+            let _ = ::std::io::Write::flush(&mut ::std::io::stdout());
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use chrono::{NaiveDateTime, TimeZone};
+    use log::Log;
+
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn emit(logger: &LogUtil, level: Level, msg: &str) {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target("log_util::tests")
+                .args(format_args!("{}", msg))
+                .build(),
+        );
+    }
+
+    /// A builder of `class_name` whose log directory was removed and whose clock starts at the
+    /// given local time
+    fn mock_clock_builder(
+        class_name: &'static str,
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        min: u32,
+        sec: u32,
+    ) -> (LogUtilBuilder, MockClock) {
+        let _ = fs::remove_dir_all(Path::new("log").join(class_name));
+        let clock = MockClock::new(
+            chrono::Local
+                .with_ymd_and_hms(year, month, day, hour, min, sec)
+                .unwrap(),
+        );
+        let builder = LogUtil::builder()
+            .class_name(class_name)
+            .clock(Arc::new(clock.clone()));
+        (builder, clock)
+    }
+
+    /// A logger of `class_name` whose latest file is the write end of a pipe, which can't seek
+    /// and keeps every written update for the returned read end. The read end reaches its end
+    /// once `out_log_file` is reset.
+    #[cfg(unix)]
+    fn pipe_logger(class_name: &'static str) -> (LogUtil, io::PipeReader) {
+        use std::os::fd::OwnedFd;
+
+        let (reader, writer) = io::pipe().unwrap();
+        let mut logger = LogUtil::new(class_name);
+        logger.out_log_file = Some(Arc::new(Mutex::new(
+            File::from(OwnedFd::from(writer)).into(),
+        )));
+        (logger, reader)
+    }
+
+    /// The logger of `mock_clock_builder`
+    fn mock_clock_logger(
+        class_name: &'static str,
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        min: u32,
+        sec: u32,
+    ) -> (LogUtil, MockClock) {
+        let (builder, clock) = mock_clock_builder(class_name, year, month, day, hour, min, sec);
+        (builder.build().unwrap(), clock)
+    }
+
+    fn read_log(class_name: &str) -> String {
+        fs::read_to_string(
+            Path::new("log")
+                .join(class_name)
+                .join(format!("{class_name}.log")),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_child_level_override() {
+        let parent: &'static LogUtil = Box::leak(Box::new(LogUtil::new("ChildLevelTest")));
+        parent.set_level(LevelFilter::Info);
+        let child = parent.child();
+        assert_eq!(child.level(), LevelFilter::Info);
+
+        child.set_level(LevelFilter::Debug);
+        emit(parent, Level::Debug, "parent debug line");
+        emit(&child, Level::Debug, "child debug line");
+
+        let content = read_log("ChildLevelTest");
+        assert!(content.contains("child debug line"));
+        assert!(!content.contains("parent debug line"));
+        assert_eq!(parent.level(), LevelFilter::Info);
+
+        child.inherit_level();
+        assert_eq!(child.level(), LevelFilter::Info);
+    }
+
+    fn assert_line_positions_match_files(logger: &LogUtil) {
+        assert_eq!(
+            logger.out_log_file.is_some(),
+            logger.out_log_file_line_position.is_some()
+        );
+        assert_eq!(
+            logger.out_log_date_file.is_some(),
+            logger.out_log_date_file_line_position.is_some()
+        );
+    }
+
+    #[test]
+    fn test_concurrent_records_keep_files_in_sync() {
+        let logger = Arc::new(LogUtil::new("FileSyncTest"));
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let logger = Arc::clone(&logger);
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        emit(&logger, Level::Info, &format!("writer {t} record {i}"));
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let latest: Vec<String> = read_log("FileSyncTest").lines().map(String::from).collect();
+        assert_eq!(latest.len(), 200);
+        assert_eq!(logger.tail_today(200).unwrap(), latest);
+    }
+
+    #[test]
+    fn test_line_positions_follow_files() {
+        assert_line_positions_match_files(&LOGGER);
+        assert_line_positions_match_files(&LogUtil::new(""));
+        assert_line_positions_match_files(&LogUtil::console_only());
+        let logger = LogUtil::new("LinePositionTest");
+        assert!(logger.out_log_file.is_some());
+        assert_line_positions_match_files(&logger);
+    }
+
+    #[test]
+    fn test_console_only() {
+        let logger = LogUtil::console_only();
+        emit(&logger, Level::Info, "console only");
+        logger.output_progress_msg(LogLevel::Info, "console progress", true);
+        assert!(logger.out_log_file.is_none());
+        assert!(logger.out_log_date_file.is_none());
+        assert_eq!(logger.current_log_path(), None);
+        assert_eq!(logger.tail_today(1).unwrap(), Vec::<String>::new());
+        assert!(!Path::new("log").join(".log").exists());
+    }
+
+    #[test]
+    fn test_split_date_file_name() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 8);
+        let split = |name| split_date_file_name("App", name);
+        assert_eq!(split("App_20240508.log"), Some((date.unwrap(), ".log")));
+        assert_eq!(split("App_20240508.2.log"), Some((date.unwrap(), ".2.log")));
+        assert_eq!(
+            split("App_20240508.log.gz"),
+            Some((date.unwrap(), ".log.gz"))
+        );
+        for name in [
+            "App.log",
+            "App_ERROR.log",
+            "App_2024058.log",
+            "App_20241340.log",
+            "App_+2024050.log",
+            "App_20240508",
+            "Application_20240508.log",
+            "Other_20240508.log",
+        ] {
+            assert_eq!(split(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_list_log_dates() {
+        let root = Path::new("log").join("ListDatesRoot");
+        let _ = fs::remove_dir_all(&root);
+        let logger = LogUtil::new_in("ListDates", &root);
+        let dir = root.join("ListDates");
+        for name in [
+            "ListDates_20240507.log",
+            "ListDates_20240506.log",
+            "ListDates_20240506.1.log",
+            "ListDates_20240505.log.gz",
+            "ListDates_2024050.log",
+            "ListDates_20241340.log",
+            "Other_20240504.log",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let today = chrono::Local::now().date_naive();
+        let expected: Vec<NaiveDate> = [(2024, 5, 6), (2024, 5, 7)]
+            .into_iter()
+            .map(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d).unwrap())
+            .chain([today])
+            .collect();
+        assert_eq!(logger.list_log_dates().unwrap(), expected);
+        assert!(LogUtil::console_only().list_log_dates().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_default_timestamps_have_milliseconds() {
+        let logger = LogUtil::new("MillisDefaultTest");
+        emit(&logger, Level::Info, "first");
+        std::thread::sleep(Duration::from_millis(2));
+        emit(&logger, Level::Info, "second");
+
+        let content = read_log("MillisDefaultTest");
+        let times: Vec<NaiveDateTime> = content
+            .lines()
+            .map(|line| {
+                let time = &line[1..line.find(" INFO]").unwrap()];
+                NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.3f").unwrap()
+            })
+            .collect();
+        assert_eq!(times.len(), 2, "{content}");
+        assert!(times[0] < times[1], "{content}");
+    }
+
+    #[test]
+    fn test_hide_timestamp() {
+        let logger = LogUtil::new("NoTimestampTest");
+        logger.set_show_timestamp(false);
+        emit(&logger, Level::Info, "line without timestamp");
+
+        let content = read_log("NoTimestampTest");
+        let line = content
+            .lines()
+            .find(|l| l.contains("line without timestamp"))
+            .unwrap();
+        assert_eq!(line, "[INFO] line without timestamp");
+    }
+
+    #[test]
+    fn test_field_separator() {
+        let logger = LogUtil::new("SeparatorTest");
+        logger.set_show_timestamp(false);
+        logger.set_field_separator(String::from("\t"));
+        emit(&logger, Level::Warn, "tab separated");
+
+        assert_eq!(logger.tail_today(1).unwrap(), vec!["[WARN]\ttab separated"]);
+    }
+
+    #[test]
+    fn test_reconfigure_under_concurrent_logging() {
+        let logger: &'static LogUtil = Box::leak(Box::new(LogUtil::new("ReconfigureTest")));
+        let plain = LogConfig {
+            show_timestamp: false,
+            field_separator: String::from("\t"),
+            ..LogConfig::default()
+        };
+        let piped = LogConfig {
+            show_timestamp: true,
+            field_separator: String::from(" | "),
+            ..LogConfig::default()
+        };
+        // No record may go out with the default configuration
+        logger.reconfigure(plain.clone());
+        let writer = std::thread::spawn(move || {
+            for i in 0..500 {
+                emit(logger, Level::Info, &format!("reconfigure line {i}"));
+            }
+        });
+        for i in 0..500 {
+            logger.reconfigure(if i % 2 == 0 {
+                plain.clone()
+            } else {
+                piped.clone()
+            });
+        }
+        writer.join().unwrap();
+
+        for line in logger.tail_today(500).unwrap() {
+            let consistent = line.starts_with("[INFO]\treconfigure line")
+                || (line.starts_with("[20") && line.contains(" INFO] | reconfigure line"));
+            assert!(consistent, "torn configuration in line: {line}");
+        }
+    }
+
+    #[test]
+    fn test_progress_handle() {
+        let logger = LogUtil::new("ProgressHandleTest");
+        {
+            let mut progress = logger.start_progress(LogLevel::Info);
+            for step in 1..=3 {
+                progress.update(&format!("step {step}/3"));
+            }
+        }
+        emit(&logger, Level::Info, "after progress");
+        let progress = logger.start_progress(LogLevel::Info);
+        progress.finish("finished");
+        drop(logger.start_progress(LogLevel::Info));
+
+        let content = read_log("ProgressHandleTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3, "{content}");
+        assert!(lines[0].ends_with("INFO] step 3/3"));
+        assert!(lines[1].ends_with("INFO] after progress"));
+        assert!(lines[2].ends_with("INFO] finished"));
+        assert!(content.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_dedup_repeated_records() {
+        let logger = LogUtil::new("DedupTest");
+        logger.set_dedup(true);
+        for _ in 0..5 {
+            emit(&logger, Level::Error, "connection refused");
+        }
+        emit(&logger, Level::Info, "reconnected");
+        emit(&logger, Level::Info, "reconnected");
+        logger.flush();
+
+        let content = read_log("DedupTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 4, "{content}");
+        assert!(lines[0].ends_with("ERROR] connection refused"));
+        assert!(lines[1].ends_with("ERROR] ...last message repeated 4 times"));
+        assert!(lines[2].ends_with("INFO] reconnected"));
+        assert!(lines[3].ends_with("INFO] ...last message repeated 1 times"));
+
+        // The counting goes on after a flush, without a summary of nothing
+        logger.flush();
+        emit(&logger, Level::Info, "reconnected");
+        logger.set_dedup(false);
+        emit(&logger, Level::Info, "reconnected");
+        let content = read_log("DedupTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 6, "{content}");
+        assert!(lines[4].ends_with("INFO] ...last message repeated 1 times"));
+        assert!(lines[5].ends_with("INFO] reconnected"));
+    }
+
+    #[test]
+    fn test_unwritable_root_falls_back_to_console() {
+        let dir = Path::new("log").join("UnwritableRootTest");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // A directory can't be created below a file, not even by root
+        let root = dir.join("not_a_dir");
+        fs::write(&root, "").unwrap();
+        assert!(LogUtil::builder()
+            .class_name("UnwritableRoot")
+            .root_dir(root.clone())
+            .build()
+            .is_err());
+
+        let logger = LogUtil::new_in("UnwritableRoot", &root);
+        assert!(logger.out_log_file.is_none());
+        assert!(logger.out_log_date_file.is_none());
+        let capture_path = dir.join("capture.log");
+        let capture = logger.start_capture(&capture_path).unwrap();
+        emit(&logger, Level::Warn, "still on the console");
+        capture.stop();
+        assert!(fs::read_to_string(&capture_path)
+            .unwrap()
+            .contains("WARN] still on the console"));
+    }
+
+    #[test]
+    fn test_level_align() {
+        let logger = LogUtil::new("LevelAlignTest");
+        logger.set_level(LevelFilter::Trace);
+        logger.set_show_timestamp(false);
+        logger.set_level_align(LevelAlign::Left);
+        for level in [Level::Error, Level::Warn, Level::Info, Level::Trace] {
+            emit(&logger, level, "message");
+        }
+        logger.output_progress_msg(LogLevel::Warn, "progress", true);
+        logger.set_level_align(LevelAlign::Right);
+        emit(&logger, Level::Info, "right");
+
+        let content = read_log("LevelAlignTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "[ERROR] message",
+                "[WARN ] message",
+                "[INFO ] message",
+                "[TRACE] message",
+                "[WARN ] progress",
+                "[ INFO] right",
+            ]
+        );
+        logger.set_color_enabled(false);
+        let line = render_console_line(
+            &logger.config(),
+            "",
+            LogLevel::Warn,
+            None,
+            format_args!("on the console"),
+        );
+        assert_eq!(line, "[ WARN] on the console");
+    }
+
+    #[test]
+    fn test_level_labels() {
+        let logger = LogUtil::new("LevelLabelTest");
+        logger.set_level(LevelFilter::Trace);
+        logger.set_show_timestamp(false);
+        logger.set_color_enabled(false);
+        let levels = [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ];
+        let styles = [
+            (
+                LevelLabel::Full,
+                ["ERROR", "WARN", "INFO", "DEBUG", "TRACE"],
+            ),
+            (LevelLabel::Short, ["ERR", "WRN", "INF", "DBG", "TRC"]),
+            (LevelLabel::Char, ["E", "W", "I", "D", "T"]),
+        ];
+        for (label, texts) in styles {
+            logger.set_level_label(label);
+            for (level, text) in levels.into_iter().zip(texts) {
+                emit(&logger, level, "message");
+                let line = render_console_line(
+                    &logger.config(),
+                    "",
+                    level.into(),
+                    None,
+                    format_args!("on the console"),
+                );
+                assert_eq!(line, format!("[{text}] on the console"));
+            }
+        }
+        logger.set_level_align(LevelAlign::Left);
+        emit(&logger, Level::Info, "aligned");
+
+        let content = read_log("LevelLabelTest");
+        let mut lines = content.lines();
+        for (_, texts) in styles {
+            for text in texts {
+                assert_eq!(lines.next(), Some(format!("[{text}] message").as_str()));
+            }
+        }
+        assert_eq!(lines.next(), Some("[I] aligned"));
+    }
+
+    #[test]
+    fn test_recent_keeps_last_records() {
+        let logger = LogUtil::new("RecentTest");
+        logger.set_show_timestamp(false);
+        assert!(logger.recent().is_empty());
+        emit(&logger, Level::Info, "before");
+        assert!(logger.recent().is_empty());
+
+        logger.set_recent_capacity(3);
+        for i in 0..5 {
+            emit(&logger, Level::Warn, &format!("record {i}"));
+        }
+        assert_eq!(
+            logger.recent(),
+            ["[WARN] record 2", "[WARN] record 3", "[WARN] record 4"]
+        );
+        logger.set_recent_capacity(1);
+        assert_eq!(logger.recent(), ["[WARN] record 4"]);
+    }
+
+    #[test]
+    fn test_captured_records_are_bounded() {
+        let logger = LogUtil::capturing("CapturedBoundTest");
+        logger.set_max_tracked_messages(100);
+        for i in 0..1000 {
+            emit(&logger, Level::Info, &format!("unique message {i}"));
+        }
+        let captured = logger.captured();
+        assert_eq!(captured.len(), 100);
+        assert_eq!(captured[0].1, "unique message 900");
+        assert_eq!(captured[99].1, "unique message 999");
+
+        logger.set_max_tracked_messages(10);
+        emit(&logger, Level::Warn, "after lowering the cap");
+        assert_eq!(logger.captured().len(), 10);
+    }
+
+    #[test]
+    fn test_target_filter() {
+        let logger = LogUtil::new("TargetFilterTest");
+        logger.set_level(LevelFilter::Info);
+        logger.set_target_filter(Some("net::".to_string()));
+        for (target, level, msg) in [
+            ("app::net::tcp", Level::Info, "connected"),
+            ("app::db", Level::Info, "query done"),
+            ("app::net::udp", Level::Debug, "datagram"),
+            ("net::http", Level::Warn, "slow response"),
+        ] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("{}", msg))
+                    .build(),
+            );
+        }
+        logger.set_target_filter(None);
+        emit(&logger, Level::Info, "unfiltered");
+
+        let content = read_log("TargetFilterTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3, "{content}");
+        assert!(lines[0].ends_with("INFO] connected"));
+        assert!(lines[1].ends_with("WARN] slow response"));
+        assert!(lines[2].ends_with("INFO] unfiltered"));
+    }
+
+    #[test]
+    fn test_sink_levels() {
+        let logger = LogUtil::new("SinkLevelTest");
+        logger.set_level(LevelFilter::Info);
+        logger.set_sink_level(Sink::Console, LevelFilter::Warn);
+        logger.set_sink_level(Sink::Files, LevelFilter::Debug);
+        logger.set_sink_level(Sink::Capture, LevelFilter::Warn);
+        let debug = Metadata::builder().level(Level::Debug).build();
+        assert!(logger.enabled(&debug));
+        assert!(!logger.enabled(&Metadata::builder().level(Level::Trace).build()));
+
+        let capture_path = Path::new("log").join("SinkLevelTest").join("capture.log");
+        let capture = logger.start_capture(&capture_path).unwrap();
+        emit(&logger, Level::Debug, "cache miss");
+        emit(&logger, Level::Warn, "cache full");
+        capture.stop();
+        logger.output_progress_msg(LogLevel::Debug, "warming up", true);
+
+        let content = read_log("SinkLevelTest");
+        assert!(content.contains("DEBUG] cache miss"));
+        assert!(content.contains("WARN] cache full"));
+        assert!(content.contains("DEBUG] warming up"));
+        let captured = fs::read_to_string(&capture_path).unwrap();
+        assert_eq!(captured.lines().count(), 1, "{captured}");
+        assert!(captured.contains("WARN] cache full"));
+
+        logger.inherit_sink_level(Sink::Files);
+        assert!(!logger.enabled(&debug));
+    }
+
+    #[test]
+    fn test_class_name_cannot_escape_log_dir() {
+        let error = LogUtil::try_new("../EscapeTest").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(LogUtil::try_new("..").is_err());
+        let logger = LogUtil::new("../EscapeTest");
+        assert!(logger.out_log_file.is_none());
+        assert!(!Path::new("EscapeTest").exists());
+    }
+
+    #[test]
+    fn test_absolute_log_dir() {
+        std::env::set_var("LOG_UTIL_TEST_ABSOLUTE_DIR", "instance-1");
+        let base = std::env::current_dir()
+            .unwrap()
+            .join("log")
+            .join("AbsoluteDirTarget");
+        let _ = fs::remove_dir_all(&base);
+        let logger = LogUtil::builder()
+            .class_name("AbsoluteDirTest")
+            .log_dir(base.join("${LOG_UTIL_TEST_ABSOLUTE_DIR}/$LOG_UTIL_TEST_ABSOLUTE_DIR"))
+            .build()
+            .unwrap();
+        emit(&logger, Level::Info, "outside the root");
+
+        let dir = base.join("instance-1").join("instance-1");
+        assert_eq!(
+            logger.current_log_path(),
+            Some(dir.join("AbsoluteDirTest.log"))
+        );
+        let content = fs::read_to_string(dir.join("AbsoluteDirTest.log")).unwrap();
+        assert!(content.contains("INFO] outside the root"));
+        assert!(!Path::new("log").join("AbsoluteDirTest").exists());
+
+        let unset = LogUtil::builder()
+            .class_name("AbsoluteDirTest")
+            .log_dir(base.join("$LOG_UTIL_TEST_UNSET_DIR"))
+            .build();
+        assert_eq!(unset.err().unwrap().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_console_write_ignores_closed_pipe() {
+        struct ClosedPipe;
+        impl Write for ClosedPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+        }
+        write_ignoring_errors(&mut ClosedPipe, format_args!("[INFO] {}", "lost"), true);
+        write_ignoring_errors(&mut ClosedPipe, format_args!("progress"), false);
+
+        let mut buffer = Vec::new();
+        write_ignoring_errors(&mut buffer, format_args!("[INFO] {}", "kept"), true);
+        assert_eq!(buffer, b"[INFO] kept\n");
+    }
+
+    #[test]
+    fn test_log_startup_banner() {
+        let logger = LogUtil::new("StartupBannerTest");
+        logger.log_startup_banner();
+        let content = read_log("StartupBannerTest");
+        assert_eq!(content.lines().count(), 1);
+        assert!(content.contains(&format!("INFO] {VERSION_INFO} started at ")));
+        assert!(content.contains(&format!("log_util {}", env!("CARGO_PKG_VERSION"))));
+    }
+
+    #[test]
+    fn test_log_at_runtime_level() {
+        let logger = LogUtil::new("LogAtTest");
+        logger.set_level(LevelFilter::Info);
+        for level in [LogLevel::Warn, LogLevel::Debug, LogLevel::Error] {
+            logger.log_at(level, format_args!("{} level", style::label_text(level)));
+        }
+        logger.log_msg(LogLevel::Info, "plain message");
+
+        let content = read_log("LogAtTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3, "{content}");
+        assert!(lines[0].ends_with("WARN] WARN level"));
+        assert!(lines[1].ends_with("ERROR] ERROR level"));
+        assert!(lines[2].ends_with("INFO] plain message"));
+        assert_eq!(logger.counts().total(), 3);
+    }
+
+    #[test]
+    fn test_create_nested_log_dir() {
+        let root = Path::new("log").join("NestedRootTest").join("a").join("b");
+        let _ = fs::remove_dir_all(Path::new("log").join("NestedRootTest"));
+
+        let log_dir = root.join("Nested");
+        try_create_log_dir(&log_dir, 0o750).unwrap();
+        assert!(log_dir.is_dir());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&log_dir).unwrap().permissions().mode();
+            assert_eq!(mode & !0o750 & 0o777, 0);
+        }
+    }
+
+    #[test]
+    fn test_show_delta() {
+        let logger = LogUtil::new("DeltaTest");
+        logger.set_show_delta(true);
+        emit(&logger, Level::Info, "first step");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        emit(&logger, Level::Info, "second step");
+
+        let lines = logger.tail_today(2).unwrap();
+        assert!(lines[0].contains("] [+0.000s] first step"));
+        let delta = lines[1]
+            .split("[+")
+            .nth(1)
+            .and_then(|rest| rest.split("s]").next())
+            .unwrap();
+        let delta: f64 = delta.parse().unwrap();
+        assert!(delta >= 0.02);
+
+        logger.set_delta_format(DurationFormat::Iso8601);
+        emit(&logger, Level::Info, "third step");
+        let line = logger.tail_today(1).unwrap().remove(0);
+        assert!(line.contains("] [+PT0."), "{line}");
+        assert!(line.ends_with("S] third step"), "{line}");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_show_task_id() {
+        let logger = Arc::new(LogUtil::new("TaskIdTest"));
+        logger.set_show_task_id(true);
+        emit(&logger, Level::Info, "outside of a task");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            for name in ["first", "second"] {
+                let logger = Arc::clone(&logger);
+                tokio::spawn(async move { emit(&logger, Level::Info, name) })
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let lines = logger.tail_today(3).unwrap();
+        assert!(lines[0].ends_with("] outside of a task"));
+        let task_id = |line: &str| {
+            let rest = line.split("[task ").nth(1).unwrap();
+            rest.split(']').next().unwrap().to_string()
+        };
+        assert!(lines[1].ends_with("] first"));
+        assert!(lines[2].ends_with("] second"));
+        assert_ne!(task_id(&lines[1]), task_id(&lines[2]));
+    }
+
+    #[test]
+    fn test_warnings_to_stderr() {
+        let logger = LogUtil::new("StderrTest");
+        assert!(!logger.config().console_to_stderr(LogLevel::Error));
+        logger.set_warnings_to_stderr(true);
+        let config = logger.config();
+        assert!(config.console_to_stderr(LogLevel::Error));
+        assert!(config.console_to_stderr(LogLevel::Warn));
+        assert!(!config.console_to_stderr(LogLevel::Info));
+        assert!(!config.console_to_stderr(LogLevel::Debug));
+        // The files are not affected
+        emit(&logger, Level::Warn, "on stderr");
+        assert!(read_log("StderrTest").ends_with("WARN] on stderr\n"));
+        output_warn_log_ln!(@stderr "now", "macro on stderr");
+    }
+
+    #[test]
+    fn test_async_logger() {
+        let logger = Arc::new(LogUtil::new_async("AsyncTest"));
+        logger.set_show_thread(true);
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let logger = Arc::clone(&logger);
+                std::thread::Builder::new()
+                    .name(format!("async-{i}"))
+                    .spawn(move || {
+                        for n in 0..50 {
+                            emit(&logger, Level::Info, &format!("record {n}"));
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        logger.flush();
+        let content = read_log("AsyncTest");
+        assert_eq!(content.lines().count(), 200);
+        // The records keep the thread they were logged from, not the writer thread
+        assert!(content.contains("INFO] [async-3] record 49"));
+
+        emit(&logger, Level::Warn, "written on drop");
+        drop(Arc::into_inner(logger).unwrap());
+        assert!(read_log("AsyncTest").ends_with("] written on drop\n"));
+    }
+
+    #[test]
+    fn test_show_thread() {
+        let logger = Arc::new(LogUtil::new("ShowThreadTest"));
+        logger.set_show_thread(true);
+        let workers: Vec<_> = ["worker-1", "worker-2"]
+            .into_iter()
+            .map(|name| {
+                let logger = Arc::clone(&logger);
+                std::thread::Builder::new()
+                    .name(name.to_string())
+                    .spawn(move || emit(&logger, Level::Info, "working"))
+                    .unwrap()
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let content = read_log("ShowThreadTest");
+        assert!(content.contains("INFO] [worker-1] working"), "{content}");
+        assert!(content.contains("INFO] [worker-2] working"), "{content}");
+        logger.set_show_thread(false);
+        emit(&logger, Level::Info, "plain");
+        assert!(read_log("ShowThreadTest").ends_with("INFO] plain\n"));
+    }
+
+    #[test]
+    fn test_show_location_independent_of_profile() {
+        let logger = LogUtil::new("ShowLocationTest");
+        assert_eq!(logger.config().show_location, !IS_RELEASE_BUILD);
+        let mut event = LogEvent::from_record(
+            &Record::builder()
+                .level(Level::Error)
+                .file(Some("src/db.rs"))
+                .line(Some(42))
+                .args(format_args!("query failed"))
+                .build(),
+        );
+        logger.set_show_location(true);
+        assert_eq!(
+            console_location(&logger.config(), &event),
+            "[src/db.rs:42] "
+        );
+        logger.set_show_location(false);
+        assert_eq!(console_location(&logger.config(), &event), "");
+        logger.set_show_location(true);
+        event.line = None;
+        assert_eq!(console_location(&logger.config(), &event), "");
+    }
+
+    #[test]
+    fn test_show_source_in_files() {
+        let logger = LogUtil::new("ShowSourceTest");
+        emit(&logger, Level::Info, "without source");
+        logger.set_show_source(true);
+        for (level, line) in [(Level::Info, 17), (Level::Error, 42)] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("app")
+                    .module_path(Some("app::db"))
+                    .line(Some(line))
+                    .args(format_args!("query done"))
+                    .build(),
+            );
+        }
+        emit(&logger, Level::Warn, "no module path");
+
+        let content = read_log("ShowSourceTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert!(lines[0].ends_with("INFO] without source"));
+        assert!(
+            lines[1].ends_with("INFO] [app::db] query done"),
+            "{content}"
+        );
+        assert!(
+            lines[2].ends_with("ERROR] [app::db:42] query done"),
+            "{content}"
+        );
+        assert!(lines[3].ends_with("WARN] [log_util::tests] no module path"));
+    }
+
+    #[test]
+    fn test_utc_time_zone() {
+        let logger = LogUtil::builder()
+            .class_name("UtcTimeZoneTest")
+            .time_zone(TimeZoneMode::Utc)
+            .build()
+            .unwrap();
+        emit(&logger, Level::Info, "in utc");
+
+        let content = read_log("UtcTimeZoneTest");
+        let line = content.lines().last().unwrap();
+        let logged =
+            chrono::NaiveDateTime::parse_from_str(&line[1..20], "%Y-%m-%d %H:%M:%S").unwrap();
+        let drift = Utc::now().naive_utc() - logged;
+        assert!(drift.num_seconds().abs() <= 5, "{line}");
+        // The dated file is named after the UTC date of its records
+        assert!(Path::new("log")
+            .join("UtcTimeZoneTest")
+            .join(date_file_name("UtcTimeZoneTest", logged.date(), 0))
+            .exists());
+    }
+
+    #[test]
+    fn test_millisecond_timestamp_format() {
+        let logger = LogUtil::new("MillisFormatTest");
+        logger
+            .try_set_timestamp_format("%Y-%m-%dT%H:%M:%S%.3f")
+            .unwrap();
+        emit(&logger, Level::Info, "precise");
+
+        let content = read_log("MillisFormatTest");
+        let pattern =
+            Regex::new(r"^\[\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d{3} INFO\] precise$").unwrap();
+        assert!(
+            pattern.is_match(content.lines().last().unwrap()),
+            "{content}"
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_output_progress_log_compiles() {
+        output_progress_log!(LogLevel::Error, "progress {}%", 50);
+    }
+
+    #[test]
+    fn test_try_set_timestamp_format() {
+        let logger = LogUtil::new("TimestampFormatTest");
+        logger.try_set_timestamp_format("%Y/%m/%d").unwrap();
+        emit(&logger, Level::Info, "custom format");
+        let line = logger.tail_today(1).unwrap().remove(0);
+        let expected = format!(
+            "[{} INFO] custom format",
+            chrono::Local::now().format("%Y/%m/%d")
+        );
+        assert_eq!(line, expected);
+
+        let error = logger.try_set_timestamp_format("%Y %Q").unwrap_err();
+        assert_eq!(error.format(), "%Y %Q");
+        assert!(logger.try_set_timestamp_format("").is_err());
+        assert_eq!(logger.config().timestamp_format, "%Y/%m/%d");
+    }
+
+    #[test]
+    fn test_concurrent_rollover_happens_once() {
+        let logger = LogUtil::new("ConcurrentRolloverTest");
+        // Pretend the files were opened yesterday, the next record crosses midnight
+        let today = chrono::Local::now().date_naive();
+        *logger.out_log_date.lock().unwrap() = today.pred_opt().unwrap();
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let logger = &logger;
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        logger.output_progress_msg(LogLevel::Info, "crossing", false);
+                        emit(logger, Level::Info, &format!("thread {thread} line {i}"));
+                    }
+                });
+            }
+        });
+        logger.flush();
+
+        // A second rollover would have truncated the latest file and lost earlier lines
+        let content = read_log("ConcurrentRolloverTest");
+        let lines = content
+            .lines()
+            .filter(|line| line.contains(" line "))
+            .count();
+        assert_eq!(lines, 200, "{content}");
+        assert_eq!(*logger.out_log_date.lock().unwrap(), today);
+    }
+
+    #[test]
+    fn test_mock_clock_rollover() {
+        let dir = Path::new("log").join("MockClockTest");
+        let (logger, clock) = mock_clock_logger("MockClockTest", 2001, 2, 3, 23, 59, 30);
+        emit(&logger, Level::Info, "before midnight");
+        clock.advance(chrono::Duration::minutes(1));
+        emit(&logger, Level::Info, "after midnight");
+        logger.flush();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("MockClockTest_20010203.log")).unwrap(),
+            "[2001-02-03 23:59:30.000 INFO] before midnight\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("MockClockTest_20010204.log")).unwrap(),
+            "[2001-02-04 00:00:30.000 INFO] after midnight\n"
+        );
+        assert_eq!(
+            read_log("MockClockTest"),
+            "[2001-02-04 00:00:30.000 INFO] after midnight\n"
+        );
+    }
+
+    #[test]
+    fn test_without_main_log_only_dated_files() {
+        let dir = Path::new("log").join("NoMainLogTest");
+        let (builder, clock) = mock_clock_builder("NoMainLogTest", 2001, 2, 3, 23, 0, 0);
+        let logger = builder.main_log(false).build().unwrap();
+        logger.set_show_timestamp(false);
+        emit(&logger, Level::Info, "first day");
+        logger.output_progress_msg(LogLevel::Info, "done", true);
+        clock.advance(chrono::Duration::hours(2));
+        emit(&logger, Level::Info, "second day");
+        logger.flush();
+
+        assert!(!dir.join("NoMainLogTest.log").exists());
+        assert_eq!(logger.current_log_path(), None);
+        assert_eq!(
+            fs::read_to_string(dir.join("NoMainLogTest_20010203.log")).unwrap(),
+            "[INFO] first day\n[INFO] done\n"
+        );
+        assert_eq!(logger.read_today().unwrap(), "[INFO] second day\n");
+    }
+
+    #[test]
+    fn test_failed_rollover_suspends_files() {
+        let dir = Path::new("log").join("RolloverFailTest");
+        let _ = fs::remove_file(&dir);
+        let (logger, clock) = mock_clock_logger("RolloverFailTest", 2001, 2, 3, 23, 0, 0);
+        logger.set_show_timestamp(false);
+        emit(&logger, Level::Info, "first day");
+
+        // The log directory can't be created again while a file is in its place
+        fs::remove_dir_all(&dir).unwrap();
+        fs::write(&dir, "").unwrap();
+        clock.advance(chrono::Duration::hours(2));
+        for _ in 0..MAX_FILE_WRITE_FAILURES {
+            emit(&logger, Level::Info, "lost");
+        }
+        logger.output_progress_msg(LogLevel::Info, "progress", true);
+        assert!(logger.files_suspended());
+
+        fs::remove_file(&dir).unwrap();
+        logger.recover_files();
+        emit(&logger, Level::Info, "recovered");
+        logger.flush();
+        assert!(!logger.files_suspended());
+        assert_eq!(
+            fs::read_to_string(dir.join("RolloverFailTest_20010204.log")).unwrap(),
+            "[INFO] recovered\n"
+        );
+        assert_eq!(read_log("RolloverFailTest"), "[INFO] recovered\n");
+    }
+
+    #[test]
+    fn test_rollover_resets_all_files() {
+        let dir = Path::new("log").join("RolloverAllTest");
+        let (logger, clock) = mock_clock_logger("RolloverAllTest", 2001, 2, 3, 23, 0, 0);
+        logger.set_show_timestamp(false);
+        logger.set_max_file_bytes(Some(20));
+        logger.set_split_by_level(true);
+        logger.set_dedup(true);
+        emit(&logger, Level::Info, "first part of the day");
+        emit(&logger, Level::Error, "disk full");
+        emit(&logger, Level::Error, "disk full");
+        let part = dir.join("RolloverAllTest_20010203.1.log");
+        assert_eq!(logger.current_date_log_path(), Some(part.clone()));
+
+        clock.advance(chrono::Duration::hours(2));
+        emit(&logger, Level::Warn, "next day");
+        logger.flush();
+
+        // The repeats went to the old part, the new day starts at part 0
+        assert_eq!(
+            fs::read_to_string(&part).unwrap(),
+            "[ERROR] disk full\n[ERROR] ...last message repeated 1 times\n"
+        );
+        assert_eq!(
+            logger.current_date_log_path(),
+            Some(dir.join("RolloverAllTest_20010204.log"))
+        );
+        assert_eq!(logger.out_log_date_file_index.load(Ordering::Relaxed), 0);
+        assert_eq!(logger.read_today().unwrap(), "[WARN] next day\n");
+        // The level files were reopened for the new day, the error file starts empty
+        let errors = fs::read_to_string(dir.join("RolloverAllTest_ERROR.log")).unwrap();
+        assert_eq!(errors, "");
+        let warnings = fs::read_to_string(dir.join("RolloverAllTest_WARN.log")).unwrap();
+        assert_eq!(warnings, "[WARN] next day\n");
+    }
+
+    #[test]
+    fn test_rollover_timer() {
+        let dir = Path::new("log").join("RolloverTimerTest");
+        let (logger, clock) = mock_clock_logger("RolloverTimerTest", 2001, 3, 4, 23, 59, 59);
+        let logger: &'static LogUtil = Box::leak(Box::new(logger));
+        logger.start_rollover_timer();
+        clock.advance(chrono::Duration::seconds(2));
+
+        // The files roll over without any record being logged
+        let next_day = NaiveDate::from_ymd_opt(2001, 3, 5).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while *logger.out_log_date.lock().unwrap() != next_day {
+            assert!(Instant::now() < deadline, "the timer did not roll over");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        logger.stop_rollover_timer();
+        assert!(dir.join("RolloverTimerTest_20010305.log").exists());
+
+        let console: &'static LogUtil = Box::leak(Box::new(LogUtil::new("")));
+        console.start_rollover_timer();
+        assert!(console.rollover_timer.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_append_main_log() {
+        let _ = fs::remove_dir_all(Path::new("log").join("AppendMainTest"));
+        for run in ["first run", "second run"] {
+            let logger = LogUtil::builder()
+                .class_name("AppendMainTest")
+                .main_log_mode(MainLogMode::Append)
+                .build()
+                .unwrap();
+            emit(&logger, Level::Info, run);
+        }
+
+        let content = read_log("AppendMainTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2, "{content}");
+        assert!(lines[0].ends_with("INFO] first run"));
+        assert!(lines[1].ends_with("INFO] second run"));
+    }
+
+    #[test]
+    fn test_latest_line_main_log() {
+        let logger = LogUtil::builder()
+            .class_name("LatestLineTest")
+            .main_log_mode(MainLogMode::LatestLine)
+            .build()
+            .unwrap();
+        emit(&logger, Level::Info, "older record that is longer");
+        emit(&logger, Level::Warn, "newest record");
+        let content = read_log("LatestLineTest");
+        assert!(content.ends_with("WARN] newest record\n"), "{content}");
+        assert_eq!(content.lines().count(), 1);
+
+        logger.output_progress_msg(LogLevel::Info, "halfway", false);
+        logger.flush();
+        let content = read_log("LatestLineTest");
+        assert!(content.ends_with("INFO] halfway"), "{content}");
+        assert_eq!(content.lines().count(), 1);
+        // The dated file keeps every record, but no unfinished progress
+        let today = logger.tail_today(2).unwrap();
+        assert!(
+            today[0].ends_with("INFO] older record that is longer"),
+            "{today:?}"
+        );
+        assert!(today[1].ends_with("WARN] newest record"));
+    }
+
+    #[test]
+    fn test_split_by_level() {
+        let logger = LogUtil::new("SplitLevelTest");
+        logger.set_level(LevelFilter::Trace);
+        logger.set_split_by_level(true);
+        let levels = [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ];
+        for level in levels {
+            emit(&logger, level, &format!("{level} record"));
+        }
+        logger.flush();
+
+        let dir = Path::new("log").join("SplitLevelTest");
+        for level in levels {
+            let path = dir.join(format!("SplitLevelTest_{level}.log"));
+            let content = fs::read_to_string(path).unwrap();
+            let lines: Vec<&str> = content.lines().collect();
+            assert_eq!(lines.len(), 1, "{content}");
+            assert!(lines[0].ends_with(&format!("] {level} record")));
+        }
+        assert_eq!(read_log("SplitLevelTest").lines().count(), 5);
+    }
+
+    #[test]
+    fn test_counts() {
+        let logger: &'static LogUtil = Box::leak(Box::new(LogUtil::new("CountsTest")));
+        logger.set_level(LevelFilter::Info);
+        for _ in 0..3 {
+            emit(logger, Level::Error, "failed");
+        }
+        emit(logger, Level::Warn, "slow");
+        emit(logger, Level::Debug, "filtered out");
+        logger
+            .child()
+            .output_progress_msg(LogLevel::Info, "working", true);
+
+        let counts = logger.counts();
+        assert_eq!(counts.error, 3);
+        assert_eq!(counts.warn, 1);
+        assert_eq!(counts.info, 1);
+        assert_eq!(counts.debug, 0);
+        assert_eq!(counts.total(), 5);
+        assert_eq!(logger.reset_counts(), counts);
+        assert_eq!(logger.counts(), LevelCounts::default());
+    }
+
+    #[test]
+    fn test_custom_formatter() {
+        let logger = LogUtil::new("FormatterTest");
+        logger.set_color_enabled(false);
+        logger.set_formatter(Box::new(|event| {
+            format!("{} <{}> {}", event.level, event.target, event.message)
+        }));
+        emit(&logger, Level::Warn, "custom line");
+        assert_eq!(
+            read_log("FormatterTest"),
+            "WARN <log_util::tests> custom line\n"
+        );
+
+        logger.clear_formatter();
+        emit(&logger, Level::Info, "standard line");
+        let content = read_log("FormatterTest");
+        assert!(content
+            .lines()
+            .last()
+            .unwrap()
+            .ends_with(" INFO] standard line"));
+    }
+
+    #[cfg(all(unix, feature = "syslog"))]
+    #[test]
+    fn test_with_syslog() {
+        use std::os::unix::net::UnixDatagram;
+
+        use crate::syslog::{Facility, SyslogOptions};
+
+        let dir = Path::new("log").join("SyslogTest");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("syslog.sock");
+        let daemon = UnixDatagram::bind(&path).unwrap();
+        let logger = LogUtil::with_syslog(
+            "SyslogTest",
+            SyslogOptions {
+                path,
+                facility: Facility::Daemon,
+                ..SyslogOptions::default()
+            },
+        )
+        .unwrap();
+        emit(&logger, Level::Error, "service failed");
+
+        let mut buffer = [0; 2048];
+        let len = daemon.recv(&mut buffer).unwrap();
+        let message = String::from_utf8_lossy(&buffer[..len]);
+        // daemon (3) * 8 + err (3)
+        assert!(message.starts_with("<27>1 "), "{message}");
+        assert!(message.contains(" - SyslogTest "));
+        assert!(message.ends_with("service failed"));
+        assert!(!dir.join("SyslogTest.log").exists());
+    }
+
+    #[test]
+    fn test_with_tcp_reconnects() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        use crate::tcp::TcpOptions;
+
+        // Nothing listens yet, the lines wait in the queue
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let logger = LogUtil::with_tcp_options(
+            "TcpTest",
+            addr,
+            TcpOptions {
+                reconnect_delay: Duration::from_millis(20),
+                ..TcpOptions::default()
+            },
+        );
+        logger.set_show_timestamp(false);
+        emit(&logger, Level::Info, "queued while down");
+        emit(&logger, Level::Warn, "second line");
+
+        let listener = TcpListener::bind(addr).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "[INFO] queued while down");
+        assert_eq!(lines.next().unwrap().unwrap(), "[WARN] second line");
+    }
+
+    #[test]
+    fn test_current_log_paths() {
+        let dir = Path::new("log").join("CurrentPathTest");
+        let logger = LogUtil::new("CurrentPathTest");
+        assert_eq!(
+            logger.current_log_path(),
+            Some(dir.join("CurrentPathTest.log"))
+        );
+
+        *logger.out_log_date.lock().unwrap() = NaiveDate::default();
+        emit(&logger, Level::Info, "after rollover");
+        let today = chrono::Local::now().date_naive();
+        let date_path = logger.current_date_log_path().unwrap();
+        assert_eq!(
+            date_path,
+            dir.join(date_file_name("CurrentPathTest", today, 0))
+        );
+        assert!(fs::read_to_string(date_path)
+            .unwrap()
+            .contains("after rollover"));
+
+        let single = LogUtil::single_file("CurrentPathTest", dir.join("single.log")).unwrap();
+        assert_eq!(single.current_log_path(), Some(dir.join("single.log")));
+        assert_eq!(single.current_date_log_path(), None);
+        let console = LogUtil::new("");
+        assert_eq!(console.current_log_path(), None);
+        assert_eq!(console.current_date_log_path(), None);
+    }
+
+    /// A `Vec<u8>` shared with the test, so the bytes can be checked while the logger owns it
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_with_writer() {
+        let buffer = SharedBuffer::default();
+        let logger = LogUtil::with_writer("WriterTest", buffer.clone());
+        logger.set_show_timestamp(false);
+        emit(&logger, Level::Info, "to memory");
+        // A date change must not trigger a rollover
+        *logger.out_log_date.lock().unwrap() = NaiveDate::default();
+        emit(&logger, Level::Warn, "still in memory");
+        logger.output_progress_msg(LogLevel::Info, "progress 50%", false);
+        logger.output_progress_msg(LogLevel::Info, "progress 100%", true);
+
+        let content = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            content,
+            "[INFO] to memory\n[WARN] still in memory\n[INFO] progress 50%\n[INFO] progress 100%\n"
+        );
+        assert!(!Path::new("log").join("WriterTest").exists());
+        assert_eq!(logger.current_log_path(), None);
+        assert!(logger.read_today().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_single_file_mode() {
+        let dir = Path::new("log").join("SingleFileTest");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("app.log");
+        let logger = LogUtil::single_file("SingleFileTest", &path).unwrap();
+        emit(&logger, Level::Info, "first record");
+        // A date change must not trigger a rollover
+        *logger.out_log_date.lock().unwrap() = NaiveDate::default();
+        emit(&logger, Level::Warn, "second record");
+
+        let entries: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(entries, vec![path.clone()]);
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("INFO] first record"));
+        assert!(lines[1].ends_with("WARN] second record"));
+        assert!(logger.tail_today(1).unwrap().is_empty());
+    }
+
+    /// Accepts at most `chunk` bytes per write and `capacity` bytes in total, like a nearly
+    /// full disk
+    struct ShortWriter {
+        written: Vec<u8>,
+        chunk: usize,
+        capacity: usize,
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf
+                .len()
+                .min(self.chunk)
+                .min(self.capacity - self.written.len());
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_full_line_handles_short_writes() {
+        let mut writer = ShortWriter {
+            written: Vec::new(),
+            chunk: 3,
+            capacity: 64,
+        };
+        write_full_line(&mut writer, "[INFO] complete line").unwrap();
+        assert_eq!(writer.written, b"[INFO] complete line\n");
+
+        let mut full = ShortWriter {
+            written: Vec::new(),
+            chunk: 3,
+            capacity: 8,
+        };
+        let error = write_full_line(&mut full, "[INFO] does not fit").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[cfg(feature = "color")]
+    #[test]
+    fn test_target_color() {
+        let logger = LogUtil::new("TargetColorTest");
+        logger.set_target_color("db", Color::Blue);
+        logger.set_target_color("db::pool", Color::Cyan);
+        logger.set_target_color("http", Color::Magenta);
+
+        let config = logger.config();
+        let segment = config.target_segment("db::query").unwrap();
+        assert_eq!(segment.fgcolor(), Some(Color::Blue));
+        assert_eq!(&*segment, "[db::query] ");
+        let segment = config.target_segment("db::pool::conn").unwrap();
+        assert_eq!(segment.fgcolor(), Some(Color::Cyan));
+        assert!(config.target_segment("dbx").is_none());
+        assert!(config.target_segment("app").is_none());
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let logger: &'static LogUtil = Box::leak(Box::new(LogUtil::new("HeartbeatTest")));
+        let targets = Arc::new(Mutex::new(Vec::new()));
+        {
+            let targets = Arc::clone(&targets);
+            logger.on_record(Box::new(move |event| {
+                targets.lock().unwrap().push(event.target.clone());
+            }));
+        }
+        logger.enable_heartbeat(Duration::from_millis(20), Level::Info, "alive");
+        std::thread::sleep(Duration::from_millis(150));
+        logger.disable_heartbeat();
+
+        let count = || {
+            read_log("HeartbeatTest")
+                .lines()
+                .filter(|line| line.ends_with("INFO] alive"))
+                .count()
+        };
+        let beats = count();
+        assert!(beats >= 2);
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(count(), beats);
+        assert!(targets
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|target| target == "HeartbeatTest"));
+    }
+
+    #[test]
+    fn test_redaction() {
+        let logger = LogUtil::new("RedactionTest");
+        logger.add_redaction(Regex::new(r"Bearer \w+").unwrap(), "Bearer ***");
+        logger.add_redaction(Regex::new(r"password=(\S+)").unwrap(), "password=<hidden>");
+        let capture_path = Path::new("log").join("RedactionTest").join("capture.log");
+        let capture = logger.start_capture(&capture_path).unwrap();
+        emit(
+            &logger,
+            Level::Info,
+            "auth header Bearer abc123 password=hunter2 sent",
+        );
+        capture.stop();
+
+        let expected = "INFO] auth header Bearer *** password=<hidden> sent";
+        let captured = fs::read_to_string(&capture_path).unwrap();
+        assert!(captured.trim_end().ends_with(expected));
+        assert!(read_log("RedactionTest").trim_end().ends_with(expected));
+        assert!(logger.tail_today(1).unwrap()[0].ends_with(expected));
+        assert!(!logger.read_today().unwrap().contains("abc123"));
+    }
+
+    #[test]
+    fn test_redactor() {
+        let logger = LogUtil::new("RedactorTest");
+        logger.set_show_timestamp(false);
+        let digits = Regex::new(r"\d+").unwrap();
+        logger.set_redactor(Box::new(move |message| digits.replace_all(message, "***")));
+        emit(&logger, Level::Warn, "card 4111 1111 expires 12/29");
+        logger.output_progress_msg(LogLevel::Info, "copied 512 of 1024 files", true);
+        emit(&logger, Level::Info, "no numbers here");
+
+        let content = read_log("RedactorTest");
+        assert!(!content.chars().any(|c| c.is_ascii_digit()), "{content}");
+        assert_eq!(
+            content,
+            "[WARN] card *** *** expires ***/***\n\
+             [INFO] copied *** of *** files\n\
+             [INFO] no numbers here\n"
+        );
+        logger.clear_redactor();
+        emit(&logger, Level::Info, "build 42");
+        assert!(read_log("RedactorTest").ends_with("[INFO] build 42\n"));
+    }
+
+    #[test]
+    fn test_trace_records() {
+        let logger = LogUtil::new("TraceLevelTest");
+        logger.set_level(LevelFilter::Trace);
+        emit(&logger, Level::Trace, "trace details");
+        assert!(read_log("TraceLevelTest").contains("TRACE] trace details"));
+        assert_eq!(LogLevel::from(Level::Trace), LogLevel::Trace);
+        assert_eq!(LogLevel::Trace.to_string(), "TRACE");
+    }
+
+    #[test]
+    fn test_new_in_custom_root() {
+        let root = Path::new("log").join("custom_root");
+        let _ = fs::remove_dir_all(&root);
+        let logger = LogUtil::new_in("NewInTest", &root);
+        emit(&logger, Level::Info, "before rollover");
+        // Force a rollover, the new files must be created under the same root
+        *logger.out_log_date.lock().unwrap() = NaiveDate::default();
+        emit(&logger, Level::Info, "after rollover");
+
+        let dir = root.join("NewInTest");
+        let latest = fs::read_to_string(dir.join("NewInTest.log")).unwrap();
+        assert!(latest.ends_with("INFO] after rollover\n"));
+        assert!(logger
+            .read_today()
+            .unwrap()
+            .contains("INFO] before rollover"));
+        assert!(logger
+            .read_today()
+            .unwrap()
+            .contains("INFO] after rollover"));
+        assert!(!Path::new("log").join("NewInTest").exists());
+    }
+
+    #[test]
+    fn test_try_new_reports_path() {
+        let root = Path::new("log").join("TryNewTest");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        // A regular file where the class directory should be created
+        let blocked = root.join("blocked");
+        fs::write(&blocked, "").unwrap();
+
+        let error = LogUtil::builder()
+            .class_name("TryNewTest")
+            .root_dir(blocked.clone())
+            .build()
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains(&blocked.display().to_string()));
+
+        let logger = LogUtil::builder()
+            .class_name("TryNewTest")
+            .root_dir(root.clone())
+            .build()
+            .unwrap();
+        emit(&logger, Level::Info, "created");
+        let content = fs::read_to_string(root.join("TryNewTest").join("TryNewTest.log")).unwrap();
+        assert!(content.contains("INFO] created"));
+    }
+
+    #[test]
+    fn test_flush() {
+        let logger = LogUtil::new("FlushTest");
+        emit(&logger, Level::Info, "flushed line");
+        logger.flush();
+        assert!(read_log("FlushTest").ends_with("INFO] flushed line\n"));
+        assert!(logger.tail_today(1).unwrap()[0].ends_with("INFO] flushed line"));
+        // A logger without files has nothing to flush
+        LogUtil::new("").flush();
+    }
+
+    #[test]
+    fn test_drop_flushes_files() {
+        let logger = LogUtil::new("DropFlushTest");
+        emit(&logger, Level::Info, "last line before drop");
+        drop(logger);
+        assert!(read_log("DropFlushTest").ends_with("INFO] last line before drop\n"));
+        drop(LogUtil::new(""));
+    }
+
+    #[test]
+    fn test_filtered_records_are_not_written() {
+        let logger = LogUtil::new("MaxLevelWarnTest");
+        logger.set_level(LevelFilter::Warn);
+        emit(&logger, Level::Info, "filtered info");
+        logger.output_progress_msg(LogLevel::Info, "filtered progress", true);
+        assert_eq!(read_log("MaxLevelWarnTest"), "");
+
+        emit(&logger, Level::Warn, "kept warning");
+        logger.output_progress_msg(LogLevel::Error, "kept progress", true);
+        let content = read_log("MaxLevelWarnTest");
+        assert!(content.contains("WARN] kept warning"));
+        assert!(content.contains("ERROR] kept progress"));
+        assert!(!content.contains("filtered"));
+    }
+
+    #[test]
+    fn test_builder() {
+        let root = Path::new("log").join("builder_root");
+        let _ = fs::remove_dir_all(&root);
+        let logger = LogUtil::builder()
+            .class_name("BuilderTest")
+            .root_dir(root.clone())
+            .color(false)
+            .build()
+            .unwrap();
+        assert!(!logger.config().color);
+        emit(&logger, Level::Info, "built");
+        let content = fs::read_to_string(root.join("BuilderTest").join("BuilderTest.log")).unwrap();
+        assert!(content.contains("INFO] built"));
+
+        let console_only = LogUtil::builder()
+            .class_name("BuilderNoFilesTest")
+            .with_files(false)
+            .build()
+            .unwrap();
+        assert!(console_only.out_log_file.is_none());
+        assert!(console_only.out_log_date_file.is_none());
+        assert!(!Path::new("log").join("BuilderNoFilesTest").exists());
+    }
+
+    #[cfg(feature = "color")]
+    #[test]
+    fn test_color_disabled_console_is_plain() {
+        let logger = LogUtil::new("ColorToggleTest");
+        logger.set_target_color("db", Color::Blue);
+        logger.set_color_enabled(false);
+        let config = logger.config();
+        assert!(!config.color);
+        let line = render_console_line(
+            &config,
+            "2024-05-08 12:00:00",
+            LogLevel::Warn,
+            Some("db::pool"),
+            format_args!("pool exhausted"),
+        );
+        assert_eq!(line, "[2024-05-08 12:00:00 WARN] [db::pool] pool exhausted");
+        assert!(!line.contains('\u{1b}'));
+    }
+
+    #[cfg(not(feature = "color"))]
+    #[test]
+    fn test_console_is_plain_without_color_feature() {
+        let logger = LogUtil::new("NoColorFeatureTest");
+        logger.set_color_enabled(true);
+        let line = render_console_line(
+            &logger.config(),
+            "2024-05-08 12:00:00",
+            LogLevel::Error,
+            Some("db::pool"),
+            format_args!("pool exhausted"),
+        );
+        assert_eq!(line, "[2024-05-08 12:00:00 ERROR] pool exhausted");
+    }
+
+    #[test]
+    fn test_parse_level_directives() {
+        let (global, rules) = parse_level_directives("mycrate::db=debug, warn,noisy,bad=loud");
+        assert_eq!(global, LevelFilter::Warn);
+        assert_eq!(
+            rules,
+            [
+                (String::from("mycrate::db"), LevelFilter::Debug),
+                (String::from("noisy"), LevelFilter::Trace),
+            ]
+        );
+        assert_eq!(parse_level_directives("").0, LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_parse_level_is_lenient() {
+        assert_eq!(parse_level("DEBUG"), Some(LevelFilter::Debug));
+        assert_eq!(parse_level("Info"), Some(LevelFilter::Info));
+        assert_eq!(parse_level("  warn \n"), Some(LevelFilter::Warn));
+        assert_eq!(parse_level("0"), Some(LevelFilter::Off));
+        assert_eq!(parse_level("5"), Some(LevelFilter::Trace));
+        assert_eq!(parse_level("6"), None);
+        assert_eq!(parse_level_directives(" ERROR ").0, LevelFilter::Error);
+        assert_eq!(
+            parse_level_directives("app=TRACE").1,
+            [(String::from("app"), LevelFilter::Trace)]
+        );
+    }
+
+    #[test]
+    fn test_target_levels() {
+        let logger = LogUtil::new("TargetLevelTest");
+        logger.set_level(LevelFilter::Warn);
+        logger.set_target_level("mycrate::db", LevelFilter::Debug);
+        logger.set_target_level("mycrate::db::pool", LevelFilter::Error);
+        let enabled = |target: &str, level: Level| {
+            logger.enabled(&Metadata::builder().target(target).level(level).build())
+        };
+
+        // Exact module match
+        assert!(enabled("mycrate::db", Level::Debug));
+        assert!(!enabled("mycrate::db", Level::Trace));
+        // Prefix match, the most specific prefix wins
+        assert!(enabled("mycrate::db::query", Level::Debug));
+        assert!(!enabled("mycrate::db::pool", Level::Warn));
+        assert!(enabled("mycrate::db::pool", Level::Error));
+        // Fallback to the logger's level
+        assert!(!enabled("mycrate::dbx", Level::Info));
+        assert!(!enabled("mycrate::http", Level::Info));
+        assert!(enabled("mycrate::http", Level::Warn));
+    }
+
+    #[test]
+    fn test_size_rotation() {
+        let dir = Path::new("log").join("SizeRotationTest");
+        let _ = fs::remove_dir_all(&dir);
+        let logger = LogUtil::new("SizeRotationTest");
+        logger.set_max_file_bytes(Some(200));
+        for i in 0..12 {
+            emit(
+                &logger,
+                Level::Info,
+                &format!("record {i:02} with some padding"),
+            );
+        }
+
+        let date = logger
+            .out_log_date
+            .lock()
+            .unwrap()
+            .format("%Y%m%d")
+            .to_string();
+        let part = |name: String| fs::read_to_string(dir.join(name)).unwrap();
+        let base = part(format!("SizeRotationTest_{date}.log"));
+        let first = part(format!("SizeRotationTest_{date}.1.log"));
+        let second = part(format!("SizeRotationTest_{date}.2.log"));
+        assert!(base.contains("record 00"));
+        assert!(first.lines().count() > 0);
+        assert!(second.lines().count() > 0);
+        let total: usize = [&base, &first, &second]
+            .iter()
+            .map(|content| content.lines().count())
+            .sum();
+        assert_eq!(total, 12);
+        assert!(!dir.join(format!("SizeRotationTest_{date}.3.log")).exists());
+        assert!(logger.tail_today(1).unwrap()[0].ends_with("record 11 with some padding"));
+        // The latest file is not rotated
+        assert_eq!(read_log("SizeRotationTest").lines().count(), 12);
+    }
+
+    #[test]
+    fn test_retention_removes_old_date_files() {
+        let dir = Path::new("log").join("RetentionTest");
+        let _ = fs::remove_dir_all(&dir);
+        let logger = LogUtil::new("RetentionTest");
+        logger.set_max_retained_days(Some(7));
+        let today = chrono::Local::now().date_naive();
+        let old = (today - chrono::Days::new(30)).format("%Y%m%d");
+        let recent = (today - chrono::Days::new(3)).format("%Y%m%d");
+        let kept = [
+            format!("RetentionTest_{recent}.log"),
+            String::from("RetentionTest_notadate.log"),
+            String::from("RetentionTest_2024.log"),
+            format!("Other_{old}.log"),
+            String::from("notes.txt"),
+        ];
+        let removed = [
+            format!("RetentionTest_{old}.log"),
+            format!("RetentionTest_{old}.1.log"),
+            format!("RetentionTest_{old}.log.gz"),
+        ];
+        for name in kept.iter().chain(&removed) {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        // Force a rollover, which runs the cleanup
+        *logger.out_log_date.lock().unwrap() = NaiveDate::default();
+        emit(&logger, Level::Info, "after rollover");
+
+        for name in &kept {
+            assert!(dir.join(name).exists(), "{name} was removed");
+        }
+        for name in &removed {
+            assert!(!dir.join(name).exists(), "{name} was kept");
+        }
+        assert!(logger.read_today().unwrap().contains("after rollover"));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json_format() {
+        let logger = LogUtil::new("JsonFormatTest");
+        logger.set_format(Format::Json);
+        emit(&logger, Level::Warn, "disk almost full");
+
+        let content = read_log("JsonFormatTest");
+        let line = content.lines().last().unwrap();
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(value["level"], "WARN");
+        assert_eq!(value["message"], "disk almost full");
+        assert!(value["timestamp"].is_string());
+        assert_eq!(value["target"], "log_util::tests");
+        assert!(value["line"].is_null());
+        assert!(logger.read_today().unwrap().ends_with(&format!("{line}\n")));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json_file_next_to_text() {
+        let dir = Path::new("log").join("JsonFileTest");
+        let logger = LogUtil::new("JsonFileTest");
+        logger.set_json_file(true);
+        emit(&logger, Level::Error, "disk full");
+        logger.flush();
+
+        let content = read_log("JsonFileTest");
+        let text = content.lines().last().unwrap();
+        let (prefix, message) = text.split_once("] ").unwrap();
+        let json = fs::read_to_string(dir.join("JsonFileTest.jsonl")).unwrap();
+        let value: serde_json::Value = serde_json::from_str(json.lines().last().unwrap()).unwrap();
+        assert_eq!(value["message"], message);
+        assert!(prefix.ends_with(value["level"].as_str().unwrap()), "{text}");
+        assert_eq!(
+            prefix.trim_start_matches('[').split(' ').next(),
+            value["timestamp"].as_str().unwrap().split(' ').next()
+        );
+
+        // Rolls over like the latest file
+        *logger.out_log_date.lock().unwrap() -= chrono::Duration::days(1);
+        emit(&logger, Level::Info, "next day");
+        logger.flush();
+        let json = fs::read_to_string(dir.join("JsonFileTest.jsonl")).unwrap();
+        assert_eq!(json.lines().count(), 1);
+        assert!(json.contains("next day"));
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_rolled_date_files_are_compressed() {
+        let dir = Path::new("log").join("CompressRolledTest");
+        let _ = fs::remove_dir_all(&dir);
+        let logger = LogUtil::new("CompressRolledTest");
+        logger.set_compress_rolled(true);
+        let yesterday = chrono::Local::now().date_naive() - chrono::Days::new(1);
+        let name = format!("CompressRolledTest_{}", yesterday.format("%Y%m%d"));
+        fs::write(dir.join(format!("{name}.log")), "first part\n").unwrap();
+        fs::write(dir.join(format!("{name}.1.log")), "second part\n").unwrap();
+
+        // Pretend the logger was writing yesterday's second part, then roll over
+        *logger.out_log_date.lock().unwrap() = yesterday;
+        logger.out_log_date_file_index.store(1, Ordering::Relaxed);
+        emit(&logger, Level::Info, "after rollover");
+
+        let archives = [
+            dir.join(format!("{name}.log.gz")),
+            dir.join(format!("{name}.1.log.gz")),
+        ];
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !archives.iter().all(|path| path.exists()) {
+            assert!(
+                Instant::now() < deadline,
+                "rolled files were not compressed"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!dir.join(format!("{name}.log")).exists());
+        assert!(!dir.join(format!("{name}.1.log")).exists());
+        assert_eq!(logger.read_date(yesterday).unwrap(), "first part\n");
+        assert!(logger.read_today().unwrap().contains("after rollover"));
+    }
+
+    #[test]
+    fn test_capture_session() {
+        let logger = LogUtil::new("CaptureTest");
+        let capture_path = Path::new("log").join("CaptureTest").join("capture.log");
+        let capture = logger.start_capture(&capture_path).unwrap();
+        emit(&logger, Level::Info, "inside capture");
+        capture.stop();
+        emit(&logger, Level::Info, "after capture");
+
+        let captured = fs::read_to_string(&capture_path).unwrap();
+        assert_eq!(captured.lines().count(), 1);
+        assert!(captured.contains("INFO] inside capture"));
+        let content = read_log("CaptureTest");
+        assert!(content.contains("inside capture"));
+        assert!(content.contains("after capture"));
+    }
+
+    #[test]
+    fn test_timezone_tree() {
+        let logger = LogUtil::new("TimezoneTreeTest");
+        logger
+            .add_timezone_tree("utc", FixedOffset::east_opt(0).unwrap())
+            .unwrap();
+        emit(&logger, Level::Info, "zoned record");
+
+        let date = Utc::now().format("%Y%m%d");
+        let path = Path::new("log")
+            .join("TimezoneTreeTest")
+            .join("utc")
+            .join(format!("TimezoneTreeTest_{date}.log"));
+        assert!(fs::read_to_string(path)
+            .unwrap()
+            .contains("INFO] zoned record"));
+    }
+
+    #[test]
+    fn test_max_open_zone_files() {
+        let _ = fs::remove_dir_all(Path::new("log").join("MaxOpenZoneFilesTest"));
+        let logger = LogUtil::new("MaxOpenZoneFilesTest");
+        for name in ["zone_a", "zone_b", "zone_c"] {
+            logger
+                .add_timezone_tree(name, FixedOffset::east_opt(0).unwrap())
+                .unwrap();
+        }
+        logger.set_max_open_zone_files(Some(1));
+        for i in 0..3 {
+            emit(&logger, Level::Info, &format!("zoned record {i}"));
+            let zone_trees = logger.zone_trees.lock().unwrap();
+            assert_eq!(zone_trees.iter().filter(|tree| tree.is_open()).count(), 1);
+        }
+
+        let date = Utc::now().format("%Y%m%d");
+        for name in ["zone_a", "zone_b", "zone_c"] {
+            let path = Path::new("log")
+                .join("MaxOpenZoneFilesTest")
+                .join(name)
+                .join(format!("MaxOpenZoneFilesTest_{date}.log"));
+            let content = fs::read_to_string(path).unwrap();
+            assert_eq!(
+                content.matches("zoned record").count(),
+                3,
+                "{name}: {content}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shorter_progress_erases_longer_one() {
+        let logger = LogUtil::new("ProgressEraseTest");
+        logger.output_progress_msg(LogLevel::Info, "downloading ünïcödé archive 10%", false);
+        logger.output_progress_msg(LogLevel::Info, "done", true);
+
+        let content = read_log("ProgressEraseTest");
+        assert!(content.ends_with("INFO] done\n"), "{content:?}");
+        assert!(!content.contains("archive"));
+        assert!(logger.read_today().unwrap().ends_with("INFO] done\n"));
+    }
+
+    #[test]
+    fn test_progress_stays_out_of_date_file() {
+        let logger = LogUtil::new("ProgressDateFileTest");
+        let dated = logger.current_date_log_path().unwrap();
+        let before = fs::read_to_string(&dated).unwrap();
+        logger.output_progress_msg(LogLevel::Info, "copying 10%", false);
+        emit(&logger, Level::Info, "interleaved record");
+        logger.output_progress_msg(LogLevel::Info, "copying 60%", false);
+        logger.output_progress_msg(LogLevel::Info, "copied", true);
+
+        let added = fs::read_to_string(&dated).unwrap()[before.len()..].to_string();
+        let lines: Vec<&str> = added.lines().collect();
+        assert_eq!(lines.len(), 2, "{added:?}");
+        assert!(lines[0].ends_with("INFO] interleaved record"));
+        assert!(lines[1].ends_with("INFO] copied"));
+        assert!(added.ends_with('\n'));
+
+        logger.set_progress_to_date_file(true);
+        logger.output_progress_msg(LogLevel::Info, "second copy 50%", false);
+        let content = logger.read_today().unwrap();
+        assert!(content.ends_with("INFO] second copy 50%"), "{content:?}");
+        logger.output_progress_msg(LogLevel::Info, "second copy done", true);
+        let content = logger.read_today().unwrap();
+        assert!(content.contains("INFO] copied\n"));
+        assert!(content.ends_with("INFO] second copy done\n"));
+        assert!(!content.contains("50%"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_progress_on_non_seekable_sink() {
+        use std::io::Read;
+
+        let (mut logger, mut reader) = pipe_logger("NonSeekableTest");
+        logger.output_progress_msg(LogLevel::Info, "progress 10%", false);
+        logger.output_progress_msg(LogLevel::Info, "progress 50%", false);
+        logger.output_progress_msg(LogLevel::Info, "progress 100%", true);
+        // Close the write end so the read below ends
+        logger.out_log_file = None;
+
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("INFO] progress 10%"));
+        assert!(lines[1].ends_with("INFO] progress 50%"));
+        assert!(lines[2].ends_with("INFO] progress 100%"));
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_read_compressed_date() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let logger = LogUtil::new("CompressedReadTest");
+        let path = Path::new("log")
+            .join("CompressedReadTest")
+            .join("CompressedReadTest_20010101.log.gz");
+        let mut encoder = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+        writeln!(encoder, "[2001-01-01 08:00:00 INFO] archived line").unwrap();
+        encoder.finish().unwrap();
+
+        let date = NaiveDate::from_ymd_opt(2001, 1, 1).unwrap();
+        assert_eq!(
+            logger.read_date(date).unwrap(),
+            "[2001-01-01 08:00:00 INFO] archived line\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_progress_updates_are_throttled() {
+        use std::io::Read;
+
+        // A pipe appends every written update, so the written updates can be counted
+        let (mut logger, mut reader) = pipe_logger("ProgressThrottleTest");
+        logger.set_progress_min_update_interval(Duration::from_secs(60));
+        for i in 0..100 {
+            logger.output_progress_msg(LogLevel::Info, &format!("progress {i}%"), false);
+        }
+        logger.output_progress_msg(LogLevel::Info, "progress done", true);
+        logger.out_log_file = None;
+
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("progress 0%"));
+        assert!(lines[1].ends_with("progress done"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rapid_progress_updates_are_coalesced() {
+        use std::io::Read;
+
+        let (mut logger, mut reader) = pipe_logger("ProgressCoalesceTest");
+        let interval = Duration::from_millis(50);
+        logger.set_progress_min_update_interval(interval);
+        let start = Instant::now();
+        let mut updates = 0;
+        while start.elapsed() < 4 * interval {
+            logger.output_progress_msg(LogLevel::Info, &format!("progress {updates}"), false);
+            updates += 1;
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let elapsed = start.elapsed();
+        let last = format!("progress {}", updates - 1);
+        // The latest update wins when flushing, even though it arrived too early
+        logger.flush();
+        logger.out_log_file = None;
+
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let max_writes = (elapsed.as_millis() / interval.as_millis()) as usize + 1;
+        assert!(
+            lines.len() >= 2 && lines.len() <= max_writes + 1,
+            "{lines:?}"
+        );
+        assert!(lines.len() < updates);
+        assert!(lines[0].ends_with("progress 0"));
+        assert!(lines.last().unwrap().ends_with(&last), "{lines:?}");
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let logger = LogUtil::new("HexdumpTest");
+        logger.set_level(LevelFilter::Info);
+        logger.hexdump(Level::Debug, "filtered packet", b"hidden");
+        logger.hexdump(Level::Info, "packet", b"GET / HTTP/1.1\r\nHost");
+
+        let lines = logger.tail_today(3).unwrap();
+        assert!(lines[0].ends_with("INFO] packet (20 bytes)"));
+        assert_eq!(
+            lines[1],
+            "00000000  47 45 54 20 2f 20 48 54  54 50 2f 31 2e 31 0d 0a  |GET / HTTP/1.1..|"
+        );
+        assert!(lines[2].starts_with("00000010  48 6f 73 74 "));
+        assert!(lines[2].ends_with("|Host|"));
+        assert!(!read_log("HexdumpTest").contains("filtered packet"));
+
+        // The dump is filtered by the logger's own target like `log_at`
+        logger.set_target_level("HexdumpTest", LevelFilter::Warn);
+        logger.hexdump(Level::Info, "muted packet", b"hidden");
+        assert!(!read_log("HexdumpTest").contains("muted packet"));
+    }
+
+    #[test]
+    fn test_on_record_callback() {
+        let logger = LogUtil::new("");
+        logger.set_level(LevelFilter::Info);
+        let errors = Arc::new(AtomicUsize::new(0));
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        {
+            let errors = Arc::clone(&errors);
+            let levels = Arc::clone(&levels);
+            logger.on_record(Box::new(move |event| {
+                levels.lock().unwrap().push(event.level);
+                if event.level == Level::Error {
+                    errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }));
+        }
+        emit(&logger, Level::Error, "callback error");
+        emit(&logger, Level::Info, "callback info");
+        emit(&logger, Level::Debug, "filtered out");
+
+        assert_eq!(errors.load(Ordering::Relaxed), 1);
+        assert_eq!(*levels.lock().unwrap(), vec![Level::Error, Level::Info]);
+    }
+
+    #[test]
+    fn test_on_record_callback_may_log() {
+        let logger: &'static LogUtil = Box::leak(Box::new(LogUtil::new("CallbackLogTest")));
+        logger.on_record(Box::new(move |event| {
+            if event.level == Level::Error {
+                emit(
+                    logger,
+                    Level::Info,
+                    &format!("alert sent for: {}", event.message),
+                );
+            }
+        }));
+        emit(logger, Level::Error, "disk failed");
+
+        let content = read_log("CallbackLogTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert!(lines[0].ends_with("disk failed"));
+        assert!(lines[1].ends_with("INFO] alert sent for: disk failed"));
+    }
+
+    #[test]
+    fn test_silence_console_for_target() {
+        let logger = LogUtil::new("SilencedTargetTest");
+        logger.silence_console_for_target("app::poll");
+        let capture_path = Path::new("log")
+            .join("SilencedTargetTest")
+            .join("capture.log");
+        let capture = logger.start_capture(&capture_path).unwrap();
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target("app::poll::worker")
+                .args(format_args!("polled nothing"))
+                .build(),
+        );
+
+        let config = logger.config();
+        assert!(config.is_console_silenced("app::poll::worker"));
+        assert!(!config.is_console_silenced("app::poller"));
+        assert!(!config.is_console_silenced("app"));
+        assert!(read_log("SilencedTargetTest").contains("INFO] polled nothing"));
+        capture.stop();
+        let captured = fs::read_to_string(&capture_path).unwrap();
+        assert!(captured.contains("INFO] polled nothing"), "{captured}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_suspend_and_recover_files() {
+        let (mut logger, reader) = pipe_logger("SuspendFilesTest");
+        // Writing to a pipe without a reader fails, like a volume that became read-only
+        drop(reader);
+        for i in 0..MAX_FILE_WRITE_FAILURES {
+            assert!(!logger.files_suspended());
+            emit(&logger, Level::Info, &format!("failing write {i}"));
+        }
+        assert!(logger.files_suspended());
+        emit(&logger, Level::Info, "console only");
+        assert!(!logger.read_today().unwrap().contains("console only"));
+
+        // A fresh handle of the latest file, like a volume that was mounted again
+        logger.out_log_file = LogUtil::new("SuspendFilesTest").out_log_file.clone();
+        logger.recover_files();
+        emit(&logger, Level::Info, "files recovered");
+        assert!(!logger.files_suspended());
+        assert!(logger.read_today().unwrap().contains("files recovered"));
+        assert!(read_log("SuspendFilesTest").contains("files recovered"));
+    }
+
+    #[test]
+    fn test_flush_interval() {
+        let logger = LogUtil::new("FlushIntervalTest");
+        logger.set_flush_interval(Some(Duration::from_secs(60)));
+        emit(&logger, Level::Info, "buffered");
+        assert!(!read_log("FlushIntervalTest").contains("buffered"));
+        logger.flush();
+        assert!(read_log("FlushIntervalTest").contains("buffered"));
+
+        emit(&logger, Level::Info, "buffered again");
+        emit(&logger, Level::Error, "flushed right away");
+        assert!(read_log("FlushIntervalTest").ends_with("ERROR] flushed right away\n"));
+        // The counted positions still match the file, a progress line follows the records
+        logger.output_progress_msg(LogLevel::Info, "progress 10%", false);
+        logger.output_progress_msg(LogLevel::Info, "done", true);
+        let content = read_log("FlushIntervalTest");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].ends_with("ERROR] flushed right away"));
+        assert!(lines[3].ends_with("INFO] done"));
+    }
+
+    #[test]
+    fn test_tail_today() {
+        let logger = LogUtil::new("TailTodayTest");
+        for i in 0..1000 {
+            emit(&logger, Level::Info, &format!("tail line {i}"));
+        }
+
+        let lines = logger.tail_today(3).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("tail line 997"));
+        assert!(lines[1].ends_with("tail line 998"));
+        assert!(lines[2].ends_with("tail line 999"));
+    }
+
+    #[test]
+    fn test_read_invalid_utf8_lossy() {
+        let logger = LogUtil::new("RawBytesTest");
+        logger.write_raw_line(b"raw \xff\xfe bytes").unwrap();
+
+        let expected = "raw \u{FFFD}\u{FFFD} bytes";
+        assert_eq!(logger.tail_today(1).unwrap(), vec![expected]);
+        assert!(logger
+            .read_today()
+            .unwrap()
+            .ends_with(&format!("{expected}\n")));
+    }
 }
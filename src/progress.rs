@@ -0,0 +1,44 @@
+use crate::log_level::LogLevel;
+use crate::log_util::LogUtil;
+
+/// A progress line started with `LogUtil::start_progress`. Every `update` replaces the line
+/// on the console and in the files, and `finish` or dropping the handle ends it with a line
+/// break, so the next record starts on a line of its own.
+#[must_use = "the progress line is finished when the handle is dropped"]
+pub struct ProgressHandle<'a> {
+    logger: &'a LogUtil,
+    level: LogLevel,
+    /// The latest update, written again as the final line when dropped
+    last: Option<String>,
+}
+
+impl<'a> ProgressHandle<'a> {
+    pub(crate) fn new(logger: &'a LogUtil, level: LogLevel) -> ProgressHandle<'a> {
+        ProgressHandle {
+            logger,
+            level,
+            last: None,
+        }
+    }
+
+    /// Show `msg` as the current state
+    pub fn update(&mut self, msg: &str) {
+        self.logger.output_progress_msg(self.level, msg, false);
+        self.last = Some(msg.to_string());
+    }
+
+    /// End the progress line with `msg`, e.g. `"done"`
+    pub fn finish(mut self, msg: &str) {
+        self.last = None;
+        self.logger.output_progress_msg(self.level, msg, true);
+    }
+}
+
+/// Ends the line with the latest update, a handle without updates writes nothing
+impl Drop for ProgressHandle<'_> {
+    fn drop(&mut self) {
+        if let Some(last) = self.last.take() {
+            self.logger.output_progress_msg(self.level, &last, true);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The last formatted records of a logger, returned by `LogUtil::recent`. Holds at most
+/// `capacity` lines, the oldest line is dropped for a new one.
+#[derive(Default)]
+pub(crate) struct RecentRecords {
+    lines: Mutex<VecDeque<String>>,
+    capacity: AtomicUsize,
+}
+
+impl RecentRecords {
+    /// Whether records are kept, so the line is only rendered for a capacity above zero
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity.load(Ordering::Relaxed) > 0
+    }
+
+    pub(crate) fn push(&self, line: String) {
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        if capacity == 0 {
+            return;
+        }
+        let mut lines = self.lines.lock().unwrap();
+        while lines.len() >= capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Keep at most `capacity` lines from now on, dropping the oldest ones above it
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+        let mut lines = self.lines.lock().unwrap();
+        let excess = lines.len().saturating_sub(capacity);
+        lines.drain(..excess);
+        lines.shrink_to(capacity);
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
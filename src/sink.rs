@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+/// Destination of the latest or the dated log file of a `LogUtil`
+pub(crate) enum LogSink {
+    /// A buffered log file. `position` is where the next write lands, counted on every write
+    /// so the line positions can be read without a seek, which would flush the buffer.
+    File {
+        file: BufWriter<File>,
+        position: u64,
+    },
+    /// A writer supplied with `LogUtil::with_writer`, or a file opened for appending. It can't
+    /// seek, so progress updates are appended as lines of their own like on a pipe.
+    Writer(Box<dyn Write + Send>),
+}
+
+impl LogSink {
+    /// Flush and truncate a file sink to `len` bytes, a writer is left alone
+    pub(crate) fn set_len(&mut self, len: u64) -> io::Result<()> {
+        match self {
+            LogSink::File { file, .. } => {
+                file.flush()?;
+                file.get_ref().set_len(len)
+            }
+            LogSink::Writer(_) => Ok(()),
+        }
+    }
+}
+
+/// Buffers `file`, whose current position is where the writes continue
+impl From<File> for LogSink {
+    fn from(mut file: File) -> Self {
+        let position = file.stream_position().unwrap_or_default();
+        LogSink::File {
+            file: BufWriter::new(file),
+            position,
+        }
+    }
+}
+
+impl Write for LogSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            LogSink::File { file, position } => {
+                let written = file.write(buf)?;
+                *position += written as u64;
+                Ok(written)
+            }
+            LogSink::Writer(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            LogSink::File { file, .. } => file.flush(),
+            LogSink::Writer(writer) => writer.flush(),
+        }
+    }
+}
+
+impl Seek for LogSink {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            LogSink::File { file, position } => {
+                *position = file.seek(pos)?;
+                Ok(*position)
+            }
+            LogSink::Writer(_) => Err(unseekable()),
+        }
+    }
+
+    /// The counted position of a file, without flushing the buffer
+    fn stream_position(&mut self) -> io::Result<u64> {
+        match self {
+            LogSink::File { position, .. } => Ok(*position),
+            LogSink::Writer(_) => Err(unseekable()),
+        }
+    }
+}
+
+fn unseekable() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "a custom log writer can't seek")
+}
@@ -0,0 +1,203 @@
+//! Console styling of the levels, shared by `LogUtil` and the `output_*_log` macros.
+//!
+//! With the `color` feature (on by default) the text is colored through `colored`, which
+//! honors its global override, in the styles of the `ColorScheme` in use. Without it the text
+//! is returned unchanged.
+
+#[cfg(feature = "color")]
+use std::sync::Arc;
+
+#[cfg(feature = "color")]
+use arc_swap::ArcSwap;
+#[cfg(feature = "color")]
+use colored::{Color, Colorize};
+#[cfg(feature = "color")]
+use lazy_static::lazy_static;
+
+use crate::log_level::LogLevel;
+
+/// How much of the level name the `[timestamp LEVEL]` prefix shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LevelLabel {
+    /// `TRACE`, `DEBUG`, `INFO`, `WARN` and `ERROR`
+    #[default]
+    Full,
+    /// `TRC`, `DBG`, `INF`, `WRN` and `ERR`
+    Short,
+    /// `T`, `D`, `I`, `W` and `E`
+    Char,
+}
+
+impl LevelLabel {
+    /// Width of the longest label, e.g. `ERROR`
+    pub fn width(self) -> usize {
+        match self {
+            LevelLabel::Full => 5,
+            LevelLabel::Short => 3,
+            LevelLabel::Char => 1,
+        }
+    }
+}
+
+/// Label of `level` in the console and file prefix, e.g. `WARN`
+pub fn label_text(level: LogLevel) -> &'static str {
+    level_text(level, LevelLabel::Full)
+}
+
+/// Label of `level` in the style `label`, e.g. `WRN`. Every label of the console and the files
+/// comes from here.
+pub fn level_text(level: LogLevel, label: LevelLabel) -> &'static str {
+    match (label, level) {
+        (LevelLabel::Full, LogLevel::Trace) => "TRACE",
+        (LevelLabel::Full, LogLevel::Debug) => "DEBUG",
+        (LevelLabel::Full, LogLevel::Info) => "INFO",
+        (LevelLabel::Full, LogLevel::Warn) => "WARN",
+        (LevelLabel::Full, LogLevel::Error) => "ERROR",
+        (LevelLabel::Short, LogLevel::Trace) => "TRC",
+        (LevelLabel::Short, LogLevel::Debug) => "DBG",
+        (LevelLabel::Short, LogLevel::Info) => "INF",
+        (LevelLabel::Short, LogLevel::Warn) => "WRN",
+        (LevelLabel::Short, LogLevel::Error) => "ERR",
+        (LevelLabel::Char, LogLevel::Trace) => "T",
+        (LevelLabel::Char, LogLevel::Debug) => "D",
+        (LevelLabel::Char, LogLevel::Info) => "I",
+        (LevelLabel::Char, LogLevel::Warn) => "W",
+        (LevelLabel::Char, LogLevel::Error) => "E",
+    }
+}
+
+/// How the label and the message of one level look on the console
+#[cfg(feature = "color")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LevelStyle {
+    /// Foreground color, the terminal's own when `None`
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub dimmed: bool,
+    /// Underline the message, the label is never underlined
+    pub underline: bool,
+}
+
+#[cfg(feature = "color")]
+impl LevelStyle {
+    fn apply(&self, text: &str, underline: bool) -> String {
+        let mut styled = text.normal();
+        if let Some(color) = self.color {
+            styled = styled.color(color);
+        }
+        if self.bold {
+            styled = styled.bold();
+        }
+        if self.dimmed {
+            styled = styled.dimmed();
+        }
+        if underline {
+            styled = styled.underline();
+        }
+        styled.to_string()
+    }
+}
+
+/// Console style of every level, set with `LogUtil::set_color_scheme`. The default is trace
+/// dimmed, debug bright black with an underlined message, info plain, warn yellow and error
+/// red bold.
+#[cfg(feature = "color")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorScheme {
+    pub trace: LevelStyle,
+    pub debug: LevelStyle,
+    pub info: LevelStyle,
+    pub warn: LevelStyle,
+    pub error: LevelStyle,
+}
+
+#[cfg(feature = "color")]
+impl Default for ColorScheme {
+    fn default() -> Self {
+        ColorScheme {
+            trace: LevelStyle {
+                dimmed: true,
+                ..LevelStyle::default()
+            },
+            debug: LevelStyle {
+                color: Some(Color::BrightBlack),
+                underline: true,
+                ..LevelStyle::default()
+            },
+            info: LevelStyle::default(),
+            warn: LevelStyle {
+                color: Some(Color::Yellow),
+                ..LevelStyle::default()
+            },
+            error: LevelStyle {
+                color: Some(Color::Red),
+                bold: true,
+                ..LevelStyle::default()
+            },
+        }
+    }
+}
+
+#[cfg(feature = "color")]
+impl ColorScheme {
+    pub fn style(&self, level: LogLevel) -> &LevelStyle {
+        match level {
+            LogLevel::Trace => &self.trace,
+            LogLevel::Debug => &self.debug,
+            LogLevel::Info => &self.info,
+            LogLevel::Warn => &self.warn,
+            LogLevel::Error => &self.error,
+        }
+    }
+}
+
+#[cfg(feature = "color")]
+lazy_static! {
+    /// Shared by all loggers and the macros, like the `colored` override
+    static ref COLOR_SCHEME: ArcSwap<ColorScheme> = ArcSwap::from_pointee(ColorScheme::default());
+}
+
+#[cfg(feature = "color")]
+pub(crate) fn set_color_scheme(scheme: ColorScheme) {
+    COLOR_SCHEME.store(Arc::new(scheme));
+}
+
+/// The scheme in use, see `LogUtil::set_color_scheme`
+#[cfg(feature = "color")]
+pub fn color_scheme() -> ColorScheme {
+    **COLOR_SCHEME.load()
+}
+
+/// The styled label of `level`
+pub fn label(level: LogLevel) -> String {
+    styled_label(level, LevelLabel::Full)
+}
+
+/// The styled label of `level` in the style `label`
+#[cfg(feature = "color")]
+pub fn styled_label(level: LogLevel, label: LevelLabel) -> String {
+    COLOR_SCHEME
+        .load()
+        .style(level)
+        .apply(level_text(level, label), false)
+}
+
+/// The styled label of `level` in the style `label`
+#[cfg(not(feature = "color"))]
+pub fn styled_label(level: LogLevel, label: LevelLabel) -> String {
+    level_text(level, label).to_string()
+}
+
+/// `msg` styled for a record of `level`
+#[cfg(feature = "color")]
+pub fn message(level: LogLevel, msg: &str) -> String {
+    let scheme = COLOR_SCHEME.load();
+    let style = scheme.style(level);
+    style.apply(msg, style.underline)
+}
+
+/// `msg` styled for a record of `level`
+#[cfg(not(feature = "color"))]
+pub fn message(_level: LogLevel, msg: &str) -> String {
+    msg.to_string()
+}
@@ -0,0 +1,172 @@
+//! RFC 5424 output to the local syslog daemon, e.g. journald or rsyslog, over its unix
+//! datagram socket. Enabled with the `syslog` feature on Unix.
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local, SecondsFormat};
+use log::Level;
+
+/// Syslog facility of the messages, `User` by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Facility {
+    Kern = 0,
+    #[default]
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    Authpriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Where and how `LogUtil::with_syslog` sends the records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyslogOptions {
+    /// Socket of the syslog daemon, `/dev/log` by default
+    pub path: PathBuf,
+    pub facility: Facility,
+    /// APP-NAME of the messages, the class name of the logger when `None`
+    pub app_name: Option<String>,
+    /// Longest message sent, including the header. Longer messages are cut at a character
+    /// boundary instead of being rejected by the daemon. RFC 5424 receivers should accept
+    /// 2048 bytes, the default.
+    pub max_message_bytes: usize,
+}
+
+impl Default for SyslogOptions {
+    fn default() -> Self {
+        SyslogOptions {
+            path: PathBuf::from("/dev/log"),
+            facility: Facility::User,
+            app_name: None,
+            max_message_bytes: 2048,
+        }
+    }
+}
+
+/// Syslog severity of `level`, trace and debug records are both `debug` (7)
+pub fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// The connected socket of a syslog logger
+pub(crate) struct SyslogSink {
+    socket: Mutex<UnixDatagram>,
+    options: SyslogOptions,
+    app_name: String,
+}
+
+impl SyslogSink {
+    pub(crate) fn connect(options: SyslogOptions, class_name: &str) -> io::Result<SyslogSink> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(&options.path)?;
+        let app_name = options
+            .app_name
+            .clone()
+            .unwrap_or_else(|| class_name.to_string());
+        Ok(SyslogSink {
+            socket: Mutex::new(socket),
+            options,
+            app_name,
+        })
+    }
+
+    /// Send `msg` as one message. When the daemon was restarted the socket is connected
+    /// again and the message sent once more.
+    pub(crate) fn send(&self, level: Level, time: &DateTime<Local>, msg: &str) -> io::Result<()> {
+        let message = self.render(level, time, msg);
+        let mut socket = self.socket.lock().unwrap();
+        if socket.send(message.as_bytes()).is_ok() {
+            return Ok(());
+        }
+        let reconnected = UnixDatagram::unbound()?;
+        reconnected.connect(&self.options.path)?;
+        *socket = reconnected;
+        socket.send(message.as_bytes()).map(|_| ())
+    }
+
+    /// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG`, the hostname is
+    /// left to the daemon
+    fn render(&self, level: Level, time: &DateTime<Local>, msg: &str) -> String {
+        let priority = self.options.facility as u8 * 8 + severity(level);
+        let mut message = format!(
+            "<{}>1 {} - {} {} - - {}",
+            priority,
+            time.to_rfc3339_opts(SecondsFormat::Millis, false),
+            self.app_name,
+            std::process::id(),
+            msg
+        );
+        if message.len() > self.options.max_message_bytes {
+            let mut end = self.options.max_message_bytes;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_render_and_truncate() {
+        let dir = Path::new("log").join("SyslogRenderTest");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("syslog.sock");
+        let _daemon = UnixDatagram::bind(&path).unwrap();
+        let sink = SyslogSink::connect(
+            SyslogOptions {
+                path,
+                facility: Facility::Local3,
+                max_message_bytes: 128,
+                ..SyslogOptions::default()
+            },
+            "Render",
+        )
+        .unwrap();
+        let time = Local.with_ymd_and_hms(2024, 5, 8, 12, 24, 5).unwrap();
+
+        let message = sink.render(Level::Warn, &time, "disk almost full");
+        let expected_start = format!(
+            "<156>1 {} - Render ",
+            time.to_rfc3339_opts(SecondsFormat::Millis, false)
+        );
+        assert!(message.starts_with(&expected_start), "{message}");
+        assert!(message.ends_with(" - - disk almost full"));
+
+        let message = sink.render(Level::Error, &time, &"é".repeat(100));
+        assert!(message.len() <= 128);
+        assert!(message.ends_with('é'));
+    }
+}
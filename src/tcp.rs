@@ -0,0 +1,105 @@
+//! Newline-delimited log lines to a remote collector over TCP, see `LogUtil::with_tcp`.
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+/// What logging does when the queue of unsent lines is full, e.g. while the collector is down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhenFull {
+    /// Discard the new line, logging never waits for the network
+    #[default]
+    Drop,
+    /// Wait until the sender thread made room, no line is lost but logging stalls while the
+    /// collector is unreachable
+    Block,
+}
+
+/// Queueing and reconnecting of `LogUtil::with_tcp_options`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// Lines buffered while sending or disconnected, 1024 by default
+    pub queue_capacity: usize,
+    pub when_full: WhenFull,
+    /// Time between connection attempts, also the connect timeout. One second by default.
+    pub reconnect_delay: Duration,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        TcpOptions {
+            queue_capacity: 1024,
+            when_full: WhenFull::Drop,
+            reconnect_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The writer of a TCP logger. Every write queues one line for the sender thread, which
+/// connects on demand and keeps a line until it was written to a connection.
+pub(crate) struct TcpSender {
+    lines: SyncSender<Vec<u8>>,
+    when_full: WhenFull,
+}
+
+impl TcpSender {
+    pub(crate) fn spawn(addr: SocketAddr, options: TcpOptions) -> TcpSender {
+        let (lines, queued) = mpsc::sync_channel(options.queue_capacity);
+        thread::spawn(move || send_lines(addr, options.reconnect_delay, queued));
+        TcpSender {
+            lines,
+            when_full: options.when_full,
+        }
+    }
+}
+
+impl Write for TcpSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = buf.to_vec();
+        match self.when_full {
+            WhenFull::Drop => match self.lines.try_send(line) {
+                Ok(()) | Err(TrySendError::Full(_)) => Ok(buf.len()),
+                Err(TrySendError::Disconnected(_)) => Err(sender_stopped()),
+            },
+            WhenFull::Block => self
+                .lines
+                .send(line)
+                .map(|()| buf.len())
+                .map_err(|_| sender_stopped()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs until the logger is dropped and every queued line was sent
+fn send_lines(addr: SocketAddr, reconnect_delay: Duration, queued: Receiver<Vec<u8>>) {
+    let mut stream: Option<TcpStream> = None;
+    for line in queued {
+        loop {
+            let connected = match stream.as_mut() {
+                Some(connected) => connected,
+                None => match TcpStream::connect_timeout(&addr, reconnect_delay) {
+                    Ok(connected) => stream.insert(connected),
+                    Err(_) => {
+                        thread::sleep(reconnect_delay);
+                        continue;
+                    }
+                },
+            };
+            if connected.write_all(&line).is_ok() {
+                break;
+            }
+            // Reconnect and send the line again
+            stream = None;
+        }
+    }
+}
+
+fn sender_stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the TCP log sender stopped")
+}
@@ -0,0 +1,112 @@
+//! A `tracing_subscriber::Layer` writing tracing events through a `LogUtil`, enabled with the
+//! `tracing` feature.
+
+use std::fmt;
+
+use log::{Level, Log, Metadata};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::event::{current_thread_name, LogEvent};
+use crate::log_util::LogUtil;
+
+/// Renders tracing events like records of the `log` facade, with the same levels, formats,
+/// files and rollover. The names of the spans an event is recorded in precede its message,
+/// e.g. `handle_request:query: slow query`, and its fields become key-values.
+///
+/// `tracing_subscriber::registry().with(LogUtilLayer::new("MyApp")).init()`
+pub struct LogUtilLayer {
+    logger: LogUtil,
+}
+
+impl LogUtilLayer {
+    /// A layer writing to a new `LogUtil::new(class_name)`
+    pub fn new(class_name: &'static str) -> LogUtilLayer {
+        LogUtilLayer::from_logger(LogUtil::new(class_name))
+    }
+
+    /// A layer writing to `logger`, e.g. one made with `LogUtil::builder`
+    pub fn from_logger(logger: LogUtil) -> LogUtilLayer {
+        LogUtilLayer { logger }
+    }
+
+    /// The logger of this layer, to configure it after installing the layer
+    pub fn logger(&self) -> &LogUtil {
+        &self.logger
+    }
+}
+
+impl<S> Layer<S> for LogUtilLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = to_level(*metadata.level());
+        let enabled = self.logger.enabled(
+            &Metadata::builder()
+                .level(level)
+                .target(metadata.target())
+                .build(),
+        );
+        if !enabled {
+            return;
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let span = ctx.event_scope(event).map(|scope| {
+            scope
+                .from_root()
+                .map(|span| span.name())
+                .collect::<Vec<_>>()
+                .join(":")
+        });
+        self.logger.dispatch(LogEvent {
+            time: self.logger.now(),
+            level,
+            target: metadata.target().to_string(),
+            module_path: metadata.module_path().map(str::to_string),
+            file: metadata.file().map(str::to_string),
+            line: metadata.line(),
+            message: fields.message,
+            thread: current_thread_name(),
+            kv: fields.kv,
+            span,
+        });
+    }
+}
+
+fn to_level(level: tracing::Level) -> Level {
+    match level {
+        tracing::Level::ERROR => Level::Error,
+        tracing::Level::WARN => Level::Warn,
+        tracing::Level::INFO => Level::Info,
+        tracing::Level::DEBUG => Level::Debug,
+        tracing::Level::TRACE => Level::Trace,
+    }
+}
+
+/// The `message` field of an event and its other fields as key-values
+#[derive(Default)]
+struct Fields {
+    message: String,
+    kv: Vec<(String, String)>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => self.kv.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => self.kv.push((name.to_string(), format!("{:?}", value))),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Version and build of this crate, written by the build script.
+
+use std::fmt;
+
+/// What `LogUtil::log_startup_banner` reports, see `VERSION_INFO`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionInfo {
+    /// `CARGO_PKG_VERSION`, e.g. `0.1.0`
+    pub version: &'static str,
+    /// Commit the crate was built from, from `GIT_HASH` or `git rev-parse HEAD`. `None` when
+    /// neither was available.
+    pub git_hash: Option<&'static str>,
+    /// Cargo profile of the build, e.g. `debug` or `release`
+    pub profile: &'static str,
+    /// Day of the build, `YYYY-MM-DD`
+    pub build_date: &'static str,
+}
+
+/// e.g. `log_util 0.1.0 (commit 1a2b3c4, release build on 2024-05-08)`
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} (", env!("CARGO_PKG_NAME"), self.version)?;
+        if let Some(git_hash) = self.git_hash {
+            write!(f, "commit {git_hash}, ")?;
+        }
+        write!(f, "{} build on {})", self.profile, self.build_date)
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/version_info.rs"));
+
+/// Version and build of the log_util crate in use
+pub const VERSION_INFO: VersionInfo = VersionInfo {
+    version: env!("CARGO_PKG_VERSION"),
+    git_hash: GIT_HASH,
+    profile: BUILD_PROFILE,
+    build_date: BUILD_DATE,
+};
+
+/// Whether this is a release build, which hides the error locations on the console
+pub(crate) const IS_RELEASE_BUILD: bool = IS_RELEASE;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let info = VersionInfo {
+            version: "1.2.3",
+            git_hash: Some("1a2b3c4"),
+            profile: "release",
+            build_date: "2024-05-08",
+        };
+        assert_eq!(
+            info.to_string(),
+            "log_util 1.2.3 (commit 1a2b3c4, release build on 2024-05-08)"
+        );
+        let info = VersionInfo {
+            git_hash: None,
+            ..info
+        };
+        assert_eq!(
+            info.to_string(),
+            "log_util 1.2.3 (release build on 2024-05-08)"
+        );
+        assert_eq!(VERSION_INFO.version, env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+
+use crate::config::LogConfig;
+use crate::log_level::LogLevel;
+use crate::log_util::write_full_line;
+
+/// Orders the writes of all trees for `close_least_recently_used`
+static WRITE_TICK: AtomicU64 = AtomicU64::new(0);
+
+/// A dated file tree that rolls over on the calendar date of its own time zone,
+/// e.g. `log/<class_name>/jst/<class_name>_YYYYMMDD.log`.
+pub(crate) struct ZoneTree {
+    offset: FixedOffset,
+    dir: PathBuf,
+    class_name: &'static str,
+    current: Option<(NaiveDate, File)>,
+    last_write: u64,
+}
+
+impl ZoneTree {
+    pub(crate) fn new(class_name: &'static str, dir: PathBuf, offset: FixedOffset) -> Self {
+        ZoneTree {
+            offset,
+            dir,
+            class_name,
+            current: None,
+            last_write: 0,
+        }
+    }
+
+    /// Write one record, timestamped in this tree's zone, opening the file of the zone's
+    /// current date first when the date changed
+    pub(crate) fn write_record(
+        &mut self,
+        now: DateTime<Utc>,
+        config: &LogConfig,
+        level: impl Into<LogLevel>,
+        body: &str,
+    ) -> io::Result<()> {
+        self.last_write = WRITE_TICK.fetch_add(1, Ordering::Relaxed);
+        let zoned_now = now.with_timezone(&self.offset);
+        let date = zoned_now.date_naive();
+        let file = match &mut self.current {
+            Some((current_date, file)) if *current_date == date => file,
+            current => {
+                let path =
+                    self.dir
+                        .join(format!("{}_{}.log", self.class_name, date.format("%Y%m%d")));
+                let file = OpenOptions::new().append(true).create(true).open(path)?;
+                &mut current.insert((date, file)).1
+            }
+        };
+        let line = format!(
+            "{}{}",
+            config.format_prefix(config.format_time(&zoned_now), level),
+            body
+        );
+        write_full_line(file, &line)
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.current.is_some()
+    }
+}
+
+/// Close the files of the least recently written trees until at most `max_open` are open.
+/// A closed tree reopens its file for appending on its next record, so nothing is lost.
+pub(crate) fn close_least_recently_used(trees: &mut [ZoneTree], max_open: usize) {
+    let mut open: Vec<&mut ZoneTree> = trees.iter_mut().filter(|tree| tree.is_open()).collect();
+    if open.len() <= max_open {
+        return;
+    }
+    open.sort_by_key(|tree| tree.last_write);
+    let excess = open.len() - max_open;
+    for tree in open.into_iter().take(excess) {
+        tree.current = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_zone_trees_roll_on_their_own_date() {
+        let root = Path::new("log").join("ZoneTreeTest");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("utc")).unwrap();
+        fs::create_dir_all(root.join("jst")).unwrap();
+        let config = LogConfig::default();
+        let mut utc = ZoneTree::new(
+            "ZoneTreeTest",
+            root.join("utc"),
+            FixedOffset::east_opt(0).unwrap(),
+        );
+        let mut jst = ZoneTree::new(
+            "ZoneTreeTest",
+            root.join("jst"),
+            FixedOffset::east_opt(9 * 3600).unwrap(),
+        );
+
+        // 23:30 and 00:30 in JST, both still May 8th in UTC, then May 9th in UTC too
+        for (h, d) in [(14, 8), (15, 8), (0, 9)] {
+            let now = Utc.with_ymd_and_hms(2024, 5, d, h, 30, 0).unwrap();
+            utc.write_record(now, &config, LogLevel::Info, "zone line")
+                .unwrap();
+            jst.write_record(now, &config, LogLevel::Info, "zone line")
+                .unwrap();
+        }
+
+        let count = |zone: &str, date: &str| {
+            fs::read_to_string(root.join(zone).join(format!("ZoneTreeTest_{date}.log")))
+                .unwrap()
+                .lines()
+                .count()
+        };
+        assert_eq!(count("utc", "20240508"), 2);
+        assert_eq!(count("utc", "20240509"), 1);
+        assert_eq!(count("jst", "20240508"), 1);
+        assert_eq!(count("jst", "20240509"), 2);
+        let jst_first =
+            fs::read_to_string(root.join("jst").join("ZoneTreeTest_20240509.log")).unwrap();
+        assert!(jst_first.starts_with("[2024-05-09 00:30:00.000 INFO] zone line"));
+    }
+
+    #[test]
+    fn test_open_files_are_recycled() {
+        let root = Path::new("log").join("ZoneTreeLruTest");
+        let _ = fs::remove_dir_all(&root);
+        let config = LogConfig::default();
+        let mut trees: Vec<ZoneTree> = (0..3)
+            .map(|i| {
+                let dir = root.join(format!("zone{i}"));
+                fs::create_dir_all(&dir).unwrap();
+                ZoneTree::new("ZoneTreeLruTest", dir, FixedOffset::east_opt(0).unwrap())
+            })
+            .collect();
+
+        let now = Utc.with_ymd_and_hms(2024, 5, 8, 12, 0, 0).unwrap();
+        for record in 0..4 {
+            for i in 0..trees.len() {
+                let body = format!("record {record}");
+                trees[i]
+                    .write_record(now, &config, LogLevel::Info, &body)
+                    .unwrap();
+                close_least_recently_used(&mut trees, 1);
+                let open = trees.iter().filter(|tree| tree.current.is_some()).count();
+                assert_eq!(open, 1);
+                assert!(trees[i].current.is_some());
+            }
+        }
+
+        for i in 0..trees.len() {
+            let path = root
+                .join(format!("zone{i}"))
+                .join("ZoneTreeLruTest_20240508.log");
+            let content = fs::read_to_string(path).unwrap();
+            let lines: Vec<&str> = content.lines().collect();
+            assert_eq!(lines.len(), 4);
+            assert!(lines[3].ends_with("INFO] record 3"));
+        }
+    }
+}
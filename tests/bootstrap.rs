@@ -0,0 +1,25 @@
+use std::fs;
+use std::panic;
+
+use log::Level;
+use log_util::log_util::LogUtil;
+
+#[test]
+fn test_bootstrap() {
+    let guard = LogUtil::bootstrap("BootstrapTest").unwrap();
+    assert!(log::log_enabled!(Level::Info));
+    log::info!("bootstrapped");
+    assert!(panic::catch_unwind(|| panic!("bootstrap panic")).is_err());
+    drop(guard);
+
+    let content = fs::read_to_string("log/BootstrapTest/BootstrapTest.log").unwrap();
+    assert!(content.contains("INFO] bootstrapped"));
+    assert!(content.contains("ERROR] panicked at"));
+    assert!(content.contains("bootstrap panic"));
+    // The logger stays installed after the guard is dropped
+    assert!(log::log_enabled!(Level::Info));
+
+    // A second logger can't be installed and is dropped instead of leaked
+    let error = LogUtil::bootstrap("BootstrapAgainTest").err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+}
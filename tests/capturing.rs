@@ -0,0 +1,29 @@
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use log_util::log_level::LogLevel;
+use log_util::log_util::LogUtil;
+
+lazy_static! {
+    static ref LOG: LogUtil = LogUtil::capturing("CapturingTest");
+}
+
+#[test]
+fn test_capturing_logger() {
+    LogUtil::init_with_logger(&LOG).unwrap();
+
+    info!("starting {}", 1);
+    warn!("disk almost full");
+    error!("request failed: {}", "timeout");
+
+    assert_eq!(
+        LOG.captured(),
+        vec![
+            (LogLevel::Info, String::from("starting 1")),
+            (LogLevel::Warn, String::from("disk almost full")),
+            (LogLevel::Error, String::from("request failed: timeout")),
+        ]
+    );
+    assert!(!std::path::Path::new("log").join("CapturingTest").exists());
+    LOG.clear_captured();
+    assert!(LOG.captured().is_empty());
+}
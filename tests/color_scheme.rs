@@ -0,0 +1,51 @@
+#![cfg(feature = "color")]
+
+use colored::{Color, Colorize};
+use log_util::log_level::LogLevel;
+use log_util::log_util::LogUtil;
+use log_util::style::{self, ColorScheme, LevelStyle};
+
+#[test]
+fn test_color_scheme() {
+    let logger = LogUtil::console_only();
+    logger.set_color_enabled(true);
+
+    // The default keeps the appearance of the hard-coded colors
+    assert_eq!(style::label(LogLevel::Warn), "WARN".yellow().to_string());
+    assert_eq!(
+        style::message(LogLevel::Debug, "cache miss"),
+        "cache miss".bright_black().underline().to_string()
+    );
+    assert_eq!(
+        style::message(LogLevel::Error, "failed"),
+        "failed".red().bold().to_string()
+    );
+
+    logger.set_color_scheme(ColorScheme {
+        info: LevelStyle {
+            color: Some(Color::Blue),
+            ..LevelStyle::default()
+        },
+        warn: LevelStyle {
+            color: Some(Color::Magenta),
+            bold: true,
+            underline: true,
+            ..LevelStyle::default()
+        },
+        ..ColorScheme::default()
+    });
+    assert_eq!(style::color_scheme().info.color, Some(Color::Blue));
+    assert_eq!(style::label(LogLevel::Info), "INFO".blue().to_string());
+    assert_eq!(
+        style::label(LogLevel::Warn),
+        "WARN".magenta().bold().to_string()
+    );
+    assert_eq!(
+        style::message(LogLevel::Warn, "retrying"),
+        "retrying".magenta().bold().underline().to_string()
+    );
+    assert_eq!(
+        style::message(LogLevel::Error, "failed"),
+        "failed".red().bold().to_string()
+    );
+}
@@ -0,0 +1,27 @@
+use std::fs;
+
+use lazy_static::lazy_static;
+use log::info;
+use log_util::log_util::LogUtil;
+use regex::Regex;
+
+lazy_static! {
+    static ref LOG: LogUtil = LogUtil::new("KeyValueTest");
+}
+
+#[test]
+fn test_key_value_fields() {
+    LogUtil::init_with_logger(&LOG).unwrap();
+    LOG.add_redaction(Regex::new("secret-[a-z]+").unwrap(), "***");
+
+    info!(user_id = 42, token = "secret-abc"; "hello");
+    info!("no fields");
+
+    let content = fs::read_to_string("log/KeyValueTest/KeyValueTest.log").unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert!(
+        lines[0].ends_with("INFO] hello {user_id=42 token=***}"),
+        "{content}"
+    );
+    assert!(lines[1].ends_with("INFO] no fields"));
+}
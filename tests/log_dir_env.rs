@@ -0,0 +1,24 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use log::{Level, Log, Record};
+use log_util::log_util::LogUtil;
+
+#[test]
+fn test_log_dir_from_env() {
+    let root = Path::new("log").join("env_root");
+    let _ = fs::remove_dir_all(&root);
+    env::set_var("LOG_UTIL_DIR", &root);
+
+    let logger = LogUtil::new("LogDirEnvTest");
+    logger.log(
+        &Record::builder()
+            .level(Level::Info)
+            .args(format_args!("written below LOG_UTIL_DIR"))
+            .build(),
+    );
+
+    let content = fs::read_to_string(root.join("LogDirEnvTest").join("LogDirEnvTest.log")).unwrap();
+    assert!(content.contains("INFO] written below LOG_UTIL_DIR"));
+}
@@ -0,0 +1,27 @@
+use std::fs;
+
+use lazy_static::lazy_static;
+use log::Level;
+use log_util::log_once;
+use log_util::log_util::LogUtil;
+
+lazy_static! {
+    static ref LOG: LogUtil = LogUtil::new("LogOnceTest");
+}
+
+#[test]
+fn test_log_once() {
+    LogUtil::init_with_logger(&LOG).unwrap();
+
+    for i in 0..5 {
+        log_once!(Level::Warn, "deprecated option used ({})", i);
+    }
+
+    let content = fs::read_to_string("log/LogOnceTest/LogOnceTest.log").unwrap();
+    let lines: Vec<&str> = content
+        .lines()
+        .filter(|l| l.contains("deprecated option used"))
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].ends_with("deprecated option used (0)"));
+}
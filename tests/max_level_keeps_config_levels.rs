@@ -0,0 +1,26 @@
+use std::fs;
+
+use lazy_static::lazy_static;
+use log::LevelFilter;
+use log_util::config::Sink;
+use log_util::log_util::LogUtil;
+
+lazy_static! {
+    static ref LOG: LogUtil = LogUtil::new("MaxLevelConfigTest");
+}
+
+#[test]
+fn test_set_max_level_keeps_target_and_sink_levels() {
+    LogUtil::init_with_logger(&LOG).unwrap();
+    LOG.set_target_level("verbose", LevelFilter::Debug);
+    LOG.set_sink_level(Sink::Files, LevelFilter::Trace);
+    LogUtil::set_max_level(LevelFilter::Info);
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+
+    log::debug!(target: "verbose::net", "target debug");
+    log::trace!("file trace");
+
+    let content = fs::read_to_string("log/MaxLevelConfigTest/MaxLevelConfigTest.log").unwrap();
+    assert!(content.contains("DEBUG] target debug"), "{content}");
+    assert!(content.contains("TRACE] file trace"), "{content}");
+}
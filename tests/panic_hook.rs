@@ -0,0 +1,29 @@
+use std::panic;
+
+use lazy_static::lazy_static;
+use log_util::log_level::LogLevel;
+use log_util::log_util::LogUtil;
+
+lazy_static! {
+    static ref LOG: LogUtil = LogUtil::capturing("PanicHookTest");
+}
+
+#[test]
+fn test_panic_hook_logs_backtrace() {
+    // Read on the first capture, which is the one in the hook
+    std::env::set_var("RUST_BACKTRACE", "1");
+    LogUtil::init_with_logger(&LOG).unwrap();
+    LogUtil::install_panic_hook();
+
+    assert!(panic::catch_unwind(|| panic!("worker {} died", 3)).is_err());
+
+    let captured = LOG.captured();
+    let (level, message) = captured.last().unwrap();
+    assert_eq!(*level, LogLevel::Error);
+    assert!(
+        message.starts_with("panicked at tests/panic_hook.rs:"),
+        "{message}"
+    );
+    assert!(message.contains("worker 3 died"));
+    assert!(message.contains("\nstack backtrace:\n"));
+}
@@ -0,0 +1,25 @@
+use std::fs;
+
+use lazy_static::lazy_static;
+use log::LevelFilter;
+use log_util::log_util::LogUtil;
+
+lazy_static! {
+    static ref LOG: LogUtil = LogUtil::new("SetMaxLevelTest");
+}
+
+#[test]
+fn test_set_max_level() {
+    LogUtil::set_max_level(LevelFilter::Info);
+    LogUtil::init_with_logger(&LOG).unwrap();
+    log::debug!("hidden debug");
+
+    LogUtil::set_max_level(LevelFilter::Debug);
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+    assert_eq!(LOG.level(), LevelFilter::Debug);
+    log::debug!("visible debug");
+
+    let content = fs::read_to_string("log/SetMaxLevelTest/SetMaxLevelTest.log").unwrap();
+    assert!(!content.contains("hidden debug"));
+    assert!(content.contains("DEBUG] visible debug"));
+}
@@ -0,0 +1,24 @@
+#![cfg(feature = "tracing")]
+
+use std::fs;
+
+use log_util::tracing_layer::LogUtilLayer;
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn test_layer_writes_span_names() {
+    let subscriber = tracing_subscriber::registry().with(LogUtilLayer::new("TracingLayerTest"));
+    tracing::subscriber::with_default(subscriber, || {
+        let request = tracing::info_span!("handle_request");
+        let _request = request.enter();
+        let query = tracing::info_span!("query");
+        let _query = query.enter();
+        tracing::warn!(rows = 3, table = "users", "slow query");
+    });
+
+    let content = fs::read_to_string("log/TracingLayerTest/TracingLayerTest.log").unwrap();
+    assert!(
+        content.ends_with(" WARN] handle_request:query: slow query {rows=3 table=users}\n"),
+        "{content}"
+    );
+}
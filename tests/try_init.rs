@@ -0,0 +1,14 @@
+use lazy_static::lazy_static;
+use log_util::log_util::LogUtil;
+
+lazy_static! {
+    static ref LOG: LogUtil = LogUtil::new("");
+}
+
+#[test]
+fn test_try_init_twice() {
+    assert!(LogUtil::try_init().unwrap());
+    assert!(!LogUtil::try_init().unwrap());
+    assert!(!LogUtil::try_init_with_logger(&LOG).unwrap());
+    assert!(LogUtil::init().is_err());
+}